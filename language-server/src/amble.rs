@@ -1,18 +1,39 @@
 mod analysis;
 mod backend;
+mod cli;
+mod code_actions;
 mod formatter;
+mod graphs;
+mod indexing;
+mod inlay_hints;
+mod invalidation;
+mod keywords;
 mod queries;
+mod report;
+mod semantic_tokens;
+mod settings;
+mod signature_help;
+mod suggest;
 mod symbols;
+mod syntax;
 mod text;
 
 use backend::Backend;
+use cli::Invocation;
+use std::process::ExitCode;
 use tower_lsp::{LspService, Server};
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Invocation::Exit(code) = cli::run(&args) {
+        return code;
+    }
+
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
     let (service, socket) = LspService::new(|client| Backend::new(client));
     Server::new(stdin, stdout, socket).serve(service).await;
+    ExitCode::SUCCESS
 }
@@ -1,21 +1,49 @@
-use crate::backend::Backend;
+use crate::backend::{amble_parser, Backend};
+use crate::code_actions::undefined_symbol_data;
+use crate::graphs::{GoalGraph, WorkspaceGraphs};
+use crate::invalidation::DocumentFootprint;
+use crate::suggest::{did_you_mean, suggest_similar_ids};
 use crate::symbols::{
-    sanitize_markdown, ActionSetMetadata, CondMetadata, FlagMetadata, ItemMetadata, Movability,
-    NpcMetadata, RoomMetadata, SetMetadata, SymbolDefinition, SymbolIndex, SymbolKind,
-    SymbolLocation, SymbolMetadata, SymbolOccurrence, SymbolReference,
+    item_ability_id, npc_state_id, sanitize_markdown, split_scoped_id, ActionSetMetadata,
+    CondMetadata, FlagMetadata, GoalCondition, GoalConditionKind, GoalMetadata,
+    ItemAbilityMetadata, ItemMetadata, Movability, NpcMetadata, NpcStateMetadata, RoomExit,
+    RoomMetadata, SetMetadata, SpinnerMetadata, SymbolDefinition, SymbolIndex, SymbolKind,
+    SymbolLocation, SymbolMetadata, SymbolOccurrence, SymbolReference, TriggerMetadata,
 };
+use crate::syntax::collect_syntax_errors;
 use crate::text::Document;
-use std::collections::HashSet;
-use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use std::time::SystemTime;
 use tower_lsp::lsp_types::{
-    Diagnostic, DiagnosticSeverity, DiagnosticTag, InitializeParams, Position, Range, Url,
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag, FileChangeType,
+    FileEvent, InitializeParams, Location, NumberOrString, Position, Range, Url,
+    WorkspaceFoldersChangeEvent,
 };
-use tree_sitter::{Node, QueryCursor, StreamingIterator};
+use tree_sitter::{Node, Parser, QueryCursor, StreamingIterator};
 use walkdir::{DirEntry, WalkDir};
 
 const IGNORED_DIRECTORIES: &[&str] = &[".git", "node_modules", "target", "dist", "build"];
 const HOVER_DESCRIPTION_MAX_CHARS: usize = 100;
+/// Spinners the engine draws its own messages from, so no world file needs to reference them.
+const BUILTIN_SPINNERS: &[&str] = &[
+    "quitMsg",
+    "movement",
+    "takeVerb",
+    "npcIgnore",
+    "entityNotFound",
+    "destinationUnknown",
+    "unrecognizedCommand",
+    "noEffect",
+];
+
+/// Records a trigger moving an NPC into a state, so we can check the state has dialogue.
+#[derive(Debug, Clone)]
+pub(crate) struct NpcStateChange {
+    pub npc_id: String,
+    pub state: String,
+    pub range: Range,
+}
 
 /// Captures a `player_start` location plus source span for diagnostics.
 #[derive(Debug, Clone)]
@@ -25,6 +53,49 @@ pub(crate) struct PlayerStart {
     pub uri: Url,
 }
 
+/// A way into `to`: an exit, revealed exit or exit patch from `from`, or a `push player to`
+/// (`from` is `None`) that can fire wherever the player is.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RoomEdge {
+    pub from: Option<String>,
+    pub to: String,
+}
+
+/// What a flag use does to the flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum FlagUseKind {
+    /// `add flag`, `add seq flag` and `advance flag`.
+    Set,
+    /// Conditions, `required_flags`, overlays and goal conditions.
+    Read,
+    /// `remove flag` and `reset flag`.
+    Remove,
+}
+
+/// The definition a flag use belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum FlagUser {
+    Trigger(String),
+    ActionSet(String),
+    Condition(String),
+    Goal(String),
+    Exit {
+        room: String,
+        direction: String,
+    },
+    /// A room overlay.
+    Room(String),
+}
+
+/// One place a flag is set, read or removed, keyed by the base flag id (`quest#2` is `quest`).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FlagUse {
+    pub flag: String,
+    pub kind: FlagUseKind,
+    pub user: FlagUser,
+    pub range: Range,
+}
+
 impl Backend {
     pub(crate) fn update_workspace_roots(&self, params: &InitializeParams) {
         let mut roots = self.workspace_roots.write();
@@ -94,62 +165,217 @@ impl Backend {
             if !visited_dirs.insert(dir.clone()) {
                 continue;
             }
+            self.index_directory(&dir);
+        }
+    }
 
-            if !dir.exists() {
-                continue;
+    /// Indexes every `.amble` file under `dir` that is new or changed on disk since we last
+    /// read it, and forgets indexed files that have disappeared. Files open in the editor are
+    /// left to the editor's copy.
+    pub(crate) fn index_directory(&self, dir: &Path) {
+        if !dir.exists() {
+            return;
+        }
+
+        for (uri, path) in self.closed_documents_under(dir) {
+            if !path.exists() {
+                self.forget_document(&uri);
             }
+        }
 
-            for entry in WalkDir::new(&dir)
-                .follow_links(false)
-                .into_iter()
-                .filter_entry(|entry| should_visit_entry(entry))
-                .filter_map(|entry| entry.ok())
-            {
-                if !entry.file_type().is_file() {
-                    continue;
-                }
-                let path = entry.into_path();
-                if path.extension().and_then(|s| s.to_str()) != Some("amble") {
-                    continue;
-                }
-                if let Ok(uri) = Url::from_file_path(&path) {
-                    let uri_str = uri.to_string();
-                    if self.open_documents.contains(&uri_str) {
-                        continue;
-                    }
+        let mut parser = amble_parser();
+        for path in amble_files_under(dir) {
+            self.index_file(&mut parser, &path);
+        }
+    }
 
-                    let modified = file_modified(&path);
-                    if let Some(previous) = self.indexed_documents.get(&uri_str) {
-                        if !needs_rescan(previous.value().clone(), modified) {
-                            continue;
-                        }
-                    }
+    /// Reads and analyzes `path` with `parser` unless it is open in the editor or unchanged
+    /// since we last indexed it.
+    pub(crate) fn index_file(&self, parser: &mut Parser, path: &Path) {
+        let Ok(uri) = Url::from_file_path(path) else {
+            return;
+        };
+        let uri_str = uri.to_string();
+        if self.open_documents.contains(&uri_str) {
+            return;
+        }
 
-                    if let Ok(content) = std::fs::read_to_string(&path) {
-                        self.analyze_document(&uri, &content);
-                        self.indexed_documents.insert(uri_str, modified);
-                        continue;
-                    }
-                }
+        let modified = file_modified(path);
+        if let Some(previous) = self.indexed_documents.get(&uri_str) {
+            if !needs_rescan(previous.value().clone(), modified) {
+                return;
             }
         }
+
+        if let Ok(content) = std::fs::read_to_string(path) {
+            self.analyze_disk_copy(parser, &uri, &content, modified);
+        }
     }
 
-    pub(crate) fn analyze_document(&self, uri: &Url, text: &str) {
-        let tree = {
-            let mut parser = self.parser.lock();
-            match parser.parse(text, None) {
-                Some(tree) => tree,
-                None => {
-                    return;
+    /// Analyzes `content` read from disk for `uri`, unless the editor opened it in the
+    /// meantime; `did_open` marks documents open under the same guard.
+    fn analyze_disk_copy(
+        &self,
+        parser: &mut Parser,
+        uri: &Url,
+        content: &str,
+        modified: Option<SystemTime>,
+    ) {
+        let _guard = self.open_guard.read();
+        let uri_str = uri.to_string();
+        if self.open_documents.contains(&uri_str) {
+            return;
+        }
+        self.analyze_document_with(parser, uri, content);
+        self.indexed_documents.insert(uri_str, modified);
+    }
+
+    /// Applies a `workspace/didChangeWatchedFiles` event. Renames arrive as a delete plus a
+    /// create; deleting or moving a directory only reports the directory itself, which the
+    /// catch-all watcher registered next to the `.amble` one picks up.
+    pub(crate) fn apply_file_event(&self, event: &FileEvent) {
+        let uri_str = event.uri.to_string();
+        if self.open_documents.contains(&uri_str) {
+            return;
+        }
+        let Ok(path) = event.uri.to_file_path() else {
+            return;
+        };
+        // Builds and checkouts churn through these; the workspace scan never looks inside.
+        if self.in_ignored_directory(&path) {
+            return;
+        }
+
+        if event.typ == FileChangeType::DELETED {
+            if path.extension().and_then(|s| s.to_str()) == Some("amble") {
+                self.forget_document(&event.uri);
+            } else {
+                self.forget_documents_under(&path);
+            }
+            return;
+        }
+
+        if path.is_dir() {
+            self.index_directory(&path);
+            return;
+        }
+        if path.extension().and_then(|s| s.to_str()) != Some("amble") {
+            return;
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(content) => self.analyze_disk_copy(
+                &mut self.parser.lock(),
+                &event.uri,
+                &content,
+                file_modified(&path),
+            ),
+            Err(_) => self.forget_document(&event.uri),
+        }
+    }
+
+    /// Whether `path` is, or lies inside, one of the `IGNORED_DIRECTORIES` below the workspace
+    /// root holding it.
+    fn in_ignored_directory(&self, path: &Path) -> bool {
+        let roots = self.workspace_roots.read();
+        let relative = roots
+            .iter()
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .min_by_key(|relative| relative.components().count())
+            .unwrap_or(path);
+        relative.components().any(|component| match component {
+            Component::Normal(name) => name.to_str().is_some_and(is_ignored_directory),
+            _ => false,
+        })
+    }
+
+    /// Keeps `workspace_roots` in step with `workspace/didChangeWorkspaceFolders`, dropping
+    /// files under removed folders and indexing added ones.
+    pub(crate) fn apply_workspace_folder_change(&self, event: &WorkspaceFoldersChangeEvent) {
+        let removed: Vec<PathBuf> = event
+            .removed
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect();
+        let added: Vec<PathBuf> = event
+            .added
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect();
+
+        {
+            let mut roots = self.workspace_roots.write();
+            roots.retain(|root| !removed.contains(root));
+            for path in &added {
+                if !roots.iter().any(|existing| existing == path) {
+                    roots.push(path.clone());
                 }
             }
-        };
+        }
+
+        for path in &removed {
+            let still_covered = self
+                .workspace_roots
+                .read()
+                .iter()
+                .any(|root| path.starts_with(root));
+            if !still_covered {
+                self.forget_documents_under(path);
+            }
+        }
+        for path in &added {
+            self.index_directory(path);
+        }
+    }
+
+    /// Forgets every indexed, closed document whose path lies under `dir`.
+    fn forget_documents_under(&self, dir: &Path) {
+        for (uri, _) in self.closed_documents_under(dir) {
+            self.forget_document(&uri);
+        }
+    }
+
+    fn closed_documents_under(&self, dir: &Path) -> Vec<(Url, PathBuf)> {
+        self.indexed_documents
+            .iter()
+            .filter(|entry| !self.open_documents.contains(entry.key()))
+            .filter_map(|entry| {
+                let uri = Url::parse(entry.key()).ok()?;
+                let path = uri.to_file_path().ok()?;
+                path.starts_with(dir).then_some((uri, path))
+            })
+            .collect()
+    }
+
+    pub(crate) fn analyze_document(&self, uri: &Url, text: &str) {
+        self.analyze_parsed_document(uri, Document::new(text.to_string()));
+    }
+
+    /// Same as `analyze_document`, but parses with `parser` rather than the shared one so
+    /// indexing workers don't wait on each other.
+    pub(crate) fn analyze_document_with(&self, parser: &mut Parser, uri: &Url, text: &str) {
+        let mut document = Document::new(text.to_string());
+        if parse_document(parser, &mut document) {
+            self.index_document(uri, document);
+        }
+    }
 
+    /// Parses `document` (incrementally when it carries an edited tree) and re-indexes it.
+    pub(crate) fn analyze_parsed_document(&self, uri: &Url, mut document: Document) {
+        if parse_document(&mut self.parser.lock(), &mut document) {
+            self.index_document(uri, document);
+        }
+    }
+
+    /// Replaces everything indexed for `uri` with what the parsed `document` contains.
+    fn index_document(&self, uri: &Url, document: Document) {
+        let Some(tree) = document.tree().cloned() else {
+            return;
+        };
         let root_node = tree.root_node();
         let uri_str = uri.to_string();
-        let document = Document::new(text.to_string());
+        let text = document.text();
 
+        let previous = self.document_footprint(uri);
         self.symbols.clear_document(uri);
         let mut occurrences = Vec::new();
 
@@ -344,6 +570,10 @@ impl Backend {
                     .parent()
                     .map(|npc_node| extract_npc_metadata(&npc_node, text))
                     .unwrap_or((None, None, None, None));
+                let dialogue_states = node
+                    .parent()
+                    .map(|npc_node| extract_npc_dialogue_states(&npc_node, text))
+                    .unwrap_or_default();
 
                 let location = SymbolLocation {
                     uri: uri.clone(),
@@ -360,6 +590,7 @@ impl Backend {
                             description,
                             location: npc_location,
                             state,
+                            dialogue_states,
                         }),
                     },
                 );
@@ -722,312 +953,829 @@ impl Backend {
             }
         }
 
-        let player_starts = collect_player_starts(&document, root_node, text, uri);
-        self.player_starts.insert(uri_str.clone(), player_starts);
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(
+            &self.queries.spinner_definitions,
+            root_node,
+            text.as_bytes(),
+        );
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let spinner_id = slice_text(text, &node).trim();
+                if spinner_id.is_empty() {
+                    continue;
+                }
 
-        self.document_symbols.insert(uri_str.clone(), occurrences);
-        self.documents.insert(uri_str, document);
-    }
+                let range = range_from_node(&document, &node);
+                let (wedge_count, total_width) = node
+                    .parent()
+                    .map(|spinner_node| extract_spinner_metadata(&spinner_node, text))
+                    .unwrap_or((0, 0));
 
-    pub(crate) fn get_symbol_at_position(
-        &self,
-        uri: &Url,
-        position: Position,
-    ) -> Option<(SymbolKind, String)> {
-        let uri_str = uri.to_string();
-        let occurrences = self.document_symbols.get(&uri_str)?;
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
 
-        for occurrence in occurrences.iter() {
-            if range_contains(&occurrence.range, position) {
-                return Some((occurrence.kind, occurrence.id.clone()));
+                self.symbols.spinners.insert_definition(
+                    spinner_id.to_string(),
+                    SymbolDefinition {
+                        location,
+                        metadata: SymbolMetadata::Spinner(SpinnerMetadata {
+                            wedge_count,
+                            total_width,
+                        }),
+                    },
+                );
+
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::Spinner,
+                    id: spinner_id.to_string(),
+                    range,
+                });
             }
         }
 
-        None
-    }
+        let mut cursor = QueryCursor::new();
+        let mut matches =
+            cursor.matches(&self.queries.spinner_references, root_node, text.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let spinner_id = slice_text(text, &node).trim();
+                if spinner_id.is_empty() {
+                    continue;
+                }
 
-    pub(crate) fn get_symbol_occurrence_at_position(
-        &self,
-        uri: &Url,
-        position: Position,
-    ) -> Option<SymbolOccurrence> {
-        let uri_str = uri.to_string();
-        let occurrences = self.document_symbols.get(&uri_str)?;
+                let range = range_from_node(&document, &node);
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
 
-        for occurrence in occurrences.iter() {
-            if range_contains(&occurrence.range, position) {
-                return Some(occurrence.clone());
+                self.symbols.spinners.add_reference(
+                    spinner_id.to_string(),
+                    SymbolReference {
+                        location,
+                        raw_id: spinner_id.to_string(),
+                    },
+                );
+
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::Spinner,
+                    id: spinner_id.to_string(),
+                    range,
+                });
             }
         }
 
-        None
-    }
+        let mut cursor = QueryCursor::new();
+        let mut matches =
+            cursor.matches(&self.queries.goal_definitions, root_node, text.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let goal_id = slice_text(text, &node).trim();
+                if goal_id.is_empty() {
+                    continue;
+                }
 
-    pub(crate) fn get_completion_context(
-        &self,
-        uri: &Url,
-        position: Position,
-    ) -> Option<SymbolKind> {
-        let uri_str = uri.to_string();
-        let doc = self.documents.get(&uri_str)?;
-        let offset = doc.offset(position)?;
-        let text = doc.text().to_string();
-        drop(doc);
+                let range = range_from_node(&document, &node);
+                let metadata = node
+                    .parent()
+                    .map(|goal_node| extract_goal_metadata(&document, &goal_node, text))
+                    .unwrap_or_default();
 
-        let tree = {
-            let mut parser = self.parser.lock();
-            parser.parse(text.as_str(), None)?
-        };
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
 
-        let root_node = tree.root_node();
-        let mut candidate_offsets = vec![offset];
-        if offset > 0 {
-            candidate_offsets.push(offset - 1);
-        }
+                self.symbols.goals.insert_definition(
+                    goal_id.to_string(),
+                    SymbolDefinition {
+                        location,
+                        metadata: SymbolMetadata::Goal(metadata),
+                    },
+                );
 
-        for candidate in candidate_offsets {
-            if let Some(node) = node_at_offset(&root_node, candidate) {
-                if let Some(symbol_type) = symbol_kind_from_syntax(node, candidate) {
-                    return Some(symbol_type);
-                }
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::Goal,
+                    id: goal_id.to_string(),
+                    range,
+                });
             }
         }
 
-        None
-    }
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&self.queries.goal_references, root_node, text.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let goal_id = slice_text(text, &node).trim();
+                if goal_id.is_empty() {
+                    continue;
+                }
 
-    pub(crate) async fn check_diagnostics(&self, uri: &Url) {
-        let uri_str = uri.to_string();
-        if !self.documents.contains_key(&uri_str) {
-            return;
-        }
+                let range = range_from_node(&document, &node);
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
 
-        let mut diagnostics = Vec::new();
+                self.symbols.goals.add_reference(
+                    goal_id.to_string(),
+                    SymbolReference {
+                        location,
+                        raw_id: goal_id.to_string(),
+                    },
+                );
 
-        for entry in self.symbols.rooms.references_iter() {
-            let room_id = entry.key();
-            if !self.symbols.rooms.has_definition(room_id)
-                && !self.symbols.sets.has_definition(room_id)
-            {
-                for reference in entry.value() {
-                    if reference.location.uri == *uri {
-                        diagnostics.push(Diagnostic {
-                            range: reference.location.range,
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            code: None,
-                            code_description: None,
-                            source: Some("amble-lsp".to_string()),
-                            message: format!("Undefined room: '{}'", reference.raw_id),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        });
-                    }
-                }
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::Goal,
+                    id: goal_id.to_string(),
+                    range,
+                });
             }
         }
 
-        for entry in self.symbols.items.references_iter() {
-            let item_id = entry.key();
-            if !self.symbols.items.has_definition(item_id) {
-                for reference in entry.value() {
-                    if reference.location.uri == *uri {
-                        diagnostics.push(Diagnostic {
-                            range: reference.location.range,
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            code: None,
-                            code_description: None,
-                            source: Some("amble-lsp".to_string()),
-                            message: format!("Undefined item: '{}'", reference.raw_id),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        });
-                    }
-                }
-            }
-        }
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(
+            &self.queries.trigger_definitions,
+            root_node,
+            text.as_bytes(),
+        );
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let trigger_name = normalize_string_literal(slice_text(text, &node).trim());
+                if trigger_name.trim().is_empty() {
+                    continue;
+                }
 
-        for entry in self.symbols.npcs.references_iter() {
-            let npc_id = entry.key();
-            if !self.symbols.npcs.has_definition(npc_id) {
-                for reference in entry.value() {
-                    if reference.location.uri == *uri {
-                        diagnostics.push(Diagnostic {
-                            range: reference.location.range,
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            code: None,
-                            code_description: None,
-                            source: Some("amble-lsp".to_string()),
-                            message: format!("Undefined NPC: '{}'", reference.raw_id),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        });
-                    }
+                let range = range_from_node(&document, &node);
+                let metadata = node
+                    .parent()
+                    .map(|trigger_node| extract_trigger_metadata(&trigger_node, text))
+                    .unwrap_or(TriggerMetadata {
+                        event: None,
+                        only_once: false,
+                    });
+
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: string_contents_range(&document, &node, text),
+                };
+
+                self.symbols.triggers.insert_definition(
+                    trigger_name.clone(),
+                    SymbolDefinition {
+                        location,
+                        metadata: SymbolMetadata::Trigger(metadata),
+                    },
+                );
+
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::Trigger,
+                    id: trigger_name,
+                    range,
+                });
+            }
+        }
+
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(
+            &self.queries.npc_state_definitions,
+            root_node,
+            text.as_bytes(),
+        );
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let Some(token) = npc_state_token(capture.node) else {
+                    continue;
+                };
+                let Some(npc_id) = enclosing_npc_id(capture.node, text) else {
+                    continue;
+                };
+                let state = npc_state_name(slice_text(text, &token));
+                if state.is_empty() {
+                    continue;
                 }
+
+                let line_count = capture
+                    .node
+                    .parent()
+                    .map(|dialogue_node| {
+                        let mut cursor = dialogue_node.walk();
+                        dialogue_node
+                            .named_children(&mut cursor)
+                            .filter(|child| child.kind() == "npc_dialogue")
+                            .count()
+                    })
+                    .unwrap_or(0);
+
+                let id = npc_state_id(&npc_id, &state);
+                let range = range_from_node(&document, &token);
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
+
+                self.symbols.npc_states.insert_definition(
+                    id.clone(),
+                    SymbolDefinition {
+                        location,
+                        metadata: SymbolMetadata::NpcState(NpcStateMetadata {
+                            npc_id,
+                            state,
+                            custom: token.kind() == "custom_state",
+                            line_count,
+                        }),
+                    },
+                );
+
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::NpcState,
+                    id,
+                    range,
+                });
             }
         }
 
-        for entry in self.symbols.flags.references_iter() {
-            let flag_name = entry.key();
-            if !self.symbols.flags.has_definition(flag_name) {
-                for reference in entry.value() {
-                    if reference.location.uri == *uri {
-                        diagnostics.push(Diagnostic {
-                            range: reference.location.range,
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            code: None,
-                            code_description: None,
-                            source: Some("amble-lsp".to_string()),
-                            message: format!("Undefined flag: '{}'", reference.raw_id),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        });
-                    }
+        let mut state_changes = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(
+            &self.queries.npc_state_references,
+            root_node,
+            text.as_bytes(),
+        );
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let Some(token) = npc_state_token(capture.node) else {
+                    continue;
+                };
+                let Some(npc_id) = enclosing_npc_id(capture.node, text) else {
+                    continue;
+                };
+                let raw_state = slice_text(text, &token).trim();
+                let state = npc_state_name(raw_state);
+                // A bare `custom` means the grammar didn't accept the custom form here.
+                if state.is_empty() || state == "custom" {
+                    continue;
                 }
+
+                let range = range_from_node(&document, &token);
+                let is_state_change = capture.node.parent().is_some_and(|parent| {
+                    matches!(parent.kind(), "action_set_npc_state" | "npc_patch_state")
+                });
+                if is_state_change {
+                    state_changes.push(NpcStateChange {
+                        npc_id: npc_id.clone(),
+                        state: state.clone(),
+                        range,
+                    });
+                }
+
+                let id = npc_state_id(&npc_id, &state);
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
+
+                self.symbols.npc_states.add_reference(
+                    id.clone(),
+                    SymbolReference {
+                        location,
+                        raw_id: raw_state.to_string(),
+                    },
+                );
+
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::NpcState,
+                    id,
+                    range,
+                });
             }
         }
+        self.npc_state_changes
+            .insert(uri_str.clone(), state_changes);
 
-        for entry in self.symbols.sets.references_iter() {
-            let set_name = entry.key();
-            if !self.symbols.sets.has_definition(set_name) {
-                for reference in entry.value() {
-                    if reference.location.uri == *uri {
-                        diagnostics.push(Diagnostic {
-                            range: reference.location.range,
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            code: None,
-                            code_description: None,
-                            source: Some("amble-lsp".to_string()),
-                            message: format!("Undefined set: '{}'", reference.raw_id),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        });
-                    }
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(
+            &self.queries.item_ability_definitions,
+            root_node,
+            text.as_bytes(),
+        );
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let ability = slice_text(text, &node).trim();
+                if ability.is_empty() {
+                    continue;
                 }
+                let Some(item_id) = enclosing_item_id(node, text) else {
+                    continue;
+                };
+
+                let statement = node.parent();
+                let added_by_patch = statement
+                    .and_then(|stmt| stmt.parent())
+                    .is_some_and(|stmt| stmt.kind() == "item_patch_add_ability");
+                let target = statement
+                    .and_then(|stmt| stmt.child_by_field_name("target_id"))
+                    .map(|target| slice_text(text, &target).trim().to_string());
+
+                let id = item_ability_id(&item_id, ability);
+                let range = range_from_node(&document, &node);
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
+
+                self.symbols.item_abilities.insert_definition(
+                    id.clone(),
+                    SymbolDefinition {
+                        location,
+                        metadata: SymbolMetadata::ItemAbility(ItemAbilityMetadata {
+                            item_id,
+                            ability: ability.to_string(),
+                            target,
+                            added_by_patch,
+                        }),
+                    },
+                );
+
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::ItemAbility,
+                    id,
+                    range,
+                });
             }
         }
 
-        for entry in self.symbols.conds.references_iter() {
-            let cond_name = entry.key();
-            if !self.symbols.conds.has_definition(cond_name) {
-                for reference in entry.value() {
-                    if reference.location.uri == *uri {
-                        diagnostics.push(Diagnostic {
-                            range: reference.location.range,
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            code: None,
-                            code_description: None,
-                            source: Some("amble-lsp".to_string()),
-                            message: format!("Undefined condition alias: '{}'", reference.raw_id),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        });
-                    }
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(
+            &self.queries.item_ability_references,
+            root_node,
+            text.as_bytes(),
+        );
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let ability = slice_text(text, &node).trim();
+                if ability.is_empty() {
+                    continue;
                 }
+                let Some(item_id) = enclosing_item_id(node, text) else {
+                    continue;
+                };
+
+                let id = item_ability_id(&item_id, ability);
+                let range = range_from_node(&document, &node);
+                let location = SymbolLocation {
+                    uri: uri.clone(),
+                    range,
+                    rename_range: None,
+                };
+
+                self.symbols.item_abilities.add_reference(
+                    id.clone(),
+                    SymbolReference {
+                        location,
+                        raw_id: ability.to_string(),
+                    },
+                );
+
+                occurrences.push(SymbolOccurrence {
+                    kind: SymbolKind::ItemAbility,
+                    id,
+                    range,
+                });
             }
         }
 
-        for entry in self.symbols.action_sets.references_iter() {
-            let action_set_name = entry.key();
-            if !self.symbols.action_sets.has_definition(action_set_name) {
-                for reference in entry.value() {
-                    if reference.location.uri == *uri {
-                        diagnostics.push(Diagnostic {
-                            range: reference.location.range,
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            code: None,
-                            code_description: None,
-                            source: Some("amble-lsp".to_string()),
-                            message: format!("Undefined action set: '{}'", reference.raw_id),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        });
-                    }
+        let player_starts = collect_player_starts(&document, root_node, text, uri);
+        self.player_starts.insert(uri_str.clone(), player_starts);
+        self.room_edges
+            .insert(uri_str.clone(), collect_room_edges(root_node, text));
+        self.flag_uses.insert(
+            uri_str.clone(),
+            collect_flag_uses(&document, root_node, text),
+        );
+
+        let syntax_errors = collect_syntax_errors(&document, root_node, text);
+        self.syntax_errors.insert(uri_str.clone(), syntax_errors);
+
+        self.document_symbols.insert(uri_str.clone(), occurrences);
+        self.documents.insert(uri_str, document);
+
+        let current = self.document_footprint(uri);
+        self.invalidate_dependents(uri, &previous, &current);
+    }
+
+    /// Drops everything indexed for `uri`, e.g. once a closed file no longer exists on disk.
+    pub(crate) fn forget_document(&self, uri: &Url) {
+        let uri_str = uri.to_string();
+        let previous = self.document_footprint(uri);
+
+        self.symbols.clear_document(uri);
+        self.documents.remove(&uri_str);
+        self.document_symbols.remove(&uri_str);
+        self.player_starts.remove(&uri_str);
+        self.room_edges.remove(&uri_str);
+        self.flag_uses.remove(&uri_str);
+        self.syntax_errors.remove(&uri_str);
+        self.npc_state_changes.remove(&uri_str);
+        self.indexed_documents.remove(&uri_str);
+
+        self.invalidate_dependents(uri, &previous, &DocumentFootprint::default());
+    }
+
+    pub(crate) fn get_symbol_at_position(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<(SymbolKind, String)> {
+        let uri_str = uri.to_string();
+        let occurrences = self.document_symbols.get(&uri_str)?;
+
+        for occurrence in occurrences.iter() {
+            if range_contains(&occurrence.range, position) {
+                return Some((occurrence.kind, occurrence.id.clone()));
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn get_symbol_occurrence_at_position(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<SymbolOccurrence> {
+        let uri_str = uri.to_string();
+        let occurrences = self.document_symbols.get(&uri_str)?;
+
+        for occurrence in occurrences.iter() {
+            if range_contains(&occurrence.range, position) {
+                return Some(occurrence.clone());
+            }
+        }
+
+        None
+    }
+
+    /// Whether `occurrence` in `uri` is where its id is defined (or redefined) rather than a
+    /// reference to it.
+    pub(crate) fn is_definition_occurrence(
+        &self,
+        uri: &Url,
+        occurrence: &SymbolOccurrence,
+    ) -> bool {
+        let index = self.symbols.index(occurrence.kind);
+        let defined_here = |definition: &SymbolDefinition| {
+            definition.location.uri == *uri && definition.location.range == occurrence.range
+        };
+        index
+            .definition(&occurrence.id)
+            .is_some_and(|definition| defined_here(&definition))
+            || index
+                .duplicates(&occurrence.id)
+                .is_some_and(|duplicates| duplicates.iter().any(defined_here))
+    }
+
+    pub(crate) fn get_completion_context(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<SymbolKind> {
+        let uri_str = uri.to_string();
+        let doc = self.documents.get(&uri_str)?;
+        let offset = doc.offset(position)?;
+        let tree = doc.tree()?.clone();
+        drop(doc);
+
+        let root_node = tree.root_node();
+        let mut candidate_offsets = vec![offset];
+        if offset > 0 {
+            candidate_offsets.push(offset - 1);
+        }
+
+        for candidate in candidate_offsets {
+            if let Some(node) = node_at_offset(&root_node, candidate) {
+                if let Some(symbol_type) = symbol_kind_from_syntax(node, candidate) {
+                    return Some(symbol_type);
                 }
             }
         }
 
+        None
+    }
+
+    /// Finds the NPC or item whose states/abilities should be offered when completing at `position`.
+    pub(crate) fn get_completion_owner(
+        &self,
+        uri: &Url,
+        position: Position,
+        kind: SymbolKind,
+    ) -> Option<String> {
+        let uri_str = uri.to_string();
+        let doc = self.documents.get(&uri_str)?;
+        let offset = doc.offset(position)?;
+        let text = doc.text().to_string();
+        let tree = doc.tree()?.clone();
+        drop(doc);
+
+        let root_node = tree.root_node();
+        let mut candidate_offsets = vec![offset];
+        if offset > 0 {
+            candidate_offsets.push(offset - 1);
+        }
+
+        candidate_offsets.into_iter().find_map(|candidate| {
+            let node = node_at_offset(&root_node, candidate)?;
+            match kind {
+                SymbolKind::NpcState => enclosing_npc_id(node, &text),
+                SymbolKind::ItemAbility => enclosing_item_id(node, &text),
+                _ => None,
+            }
+        })
+    }
+
+    /// Publishes the diagnostics of `uri`. A document that is no longer indexed (say, a deleted
+    /// file) gets an empty list so the client clears what it showed before.
+    pub(crate) async fn check_diagnostics(&self, uri: &Url, graphs: &WorkspaceGraphs) {
+        let diagnostics = self.collect_diagnostics(uri, graphs).unwrap_or_default();
+
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, None)
+            .await;
+    }
+
+    /// Computes every diagnostic for an indexed document; `None` if `uri` isn't indexed.
+    /// `graphs` holds the workspace-wide goal and room graphs, built once for a batch of documents.
+    pub(crate) fn collect_diagnostics(
+        &self,
+        uri: &Url,
+        graphs: &WorkspaceGraphs,
+    ) -> Option<Vec<Diagnostic>> {
+        let uri_str = uri.to_string();
+        if !self.documents.contains_key(&uri_str) {
+            return None;
+        }
+
+        let mut diagnostics = Vec::new();
+
+        for (kind, code, label) in [
+            (SymbolKind::Room, "undefined-room", "room"),
+            (SymbolKind::Item, "undefined-item", "item"),
+            (SymbolKind::Npc, "undefined-npc", "NPC"),
+            (SymbolKind::Flag, "undefined-flag", "flag"),
+            (SymbolKind::Set, "undefined-set", "set"),
+            (
+                SymbolKind::Cond,
+                "undefined-condition-alias",
+                "condition alias",
+            ),
+            (SymbolKind::ActionSet, "undefined-action-set", "action set"),
+            (SymbolKind::Spinner, "undefined-spinner", "spinner"),
+            (SymbolKind::Goal, "undefined-goal", "goal"),
+        ] {
+            self.append_undefined_reference_diagnostics(uri, kind, code, label, &mut diagnostics);
+        }
+
+        for entry in self.symbols.item_abilities.references_iter() {
+            let ability_id = entry.key();
+            if self.symbols.item_abilities.has_definition(ability_id) {
+                continue;
+            }
+            let Some((item_id, ability)) = split_scoped_id(ability_id) else {
+                continue;
+            };
+            if !self.symbols.items.has_definition(item_id) {
+                continue;
+            }
+            let references: Vec<&SymbolReference> = entry
+                .value()
+                .iter()
+                .filter(|reference| reference.location.uri == *uri)
+                .collect();
+            if references.is_empty() {
+                continue;
+            }
+            let suggestions = suggest_similar_ids(
+                ability,
+                self.symbols
+                    .item_abilities
+                    .definitions_iter()
+                    .filter_map(|definition| match &definition.value().metadata {
+                        SymbolMetadata::ItemAbility(meta) if meta.item_id == item_id => {
+                            Some(meta.ability.clone())
+                        }
+                        _ => None,
+                    }),
+            );
+            for reference in references {
+                diagnostics.push(Diagnostic {
+                    range: reference.location.range,
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    code: rule_code("unknown-item-ability"),
+                    code_description: None,
+                    source: Some("amble-lsp".to_string()),
+                    message: format!(
+                        "Item '{}' never has ability '{}'; this trigger can't fire{}",
+                        item_id,
+                        reference.raw_id,
+                        did_you_mean(&suggestions)
+                    ),
+                    related_information: None,
+                    tags: None,
+                    data: undefined_symbol_data(
+                        SymbolKind::ItemAbility,
+                        ability_id,
+                        &suggestions,
+                        reference.location.rename_range(),
+                    ),
+                });
+            }
+        }
+
+        self.append_syntax_diagnostics(uri, &mut diagnostics);
         self.append_duplicate_definition_diagnostics(uri, &mut diagnostics);
         self.append_unused_definition_diagnostics(uri, &mut diagnostics);
         self.append_metadata_diagnostics(uri, &mut diagnostics);
         self.append_world_consistency_diagnostics(uri, &mut diagnostics);
+        self.append_reachability_diagnostics(
+            uri,
+            graphs.reachable_rooms.as_ref(),
+            &mut diagnostics,
+        );
         self.append_flag_sequence_diagnostics(uri, &mut diagnostics);
+        self.append_npc_state_diagnostics(uri, &mut diagnostics);
+        append_goal_diagnostics(&graphs.goals, uri, &mut diagnostics);
 
-        self.client
-            .publish_diagnostics(uri.clone(), diagnostics, None)
-            .await;
+        Some(diagnostics)
     }
 
-    pub(crate) async fn check_workspace_diagnostics(&self) {
-        let uris: Vec<Url> = self
-            .documents
-            .iter()
-            .filter_map(|entry| Url::parse(entry.key()).ok())
-            .collect();
+    /// Errors for references in `uri` to `kind` ids nothing defines, each offering similar
+    /// defined ids. Suggestions are only computed for ids `uri` actually references.
+    fn append_undefined_reference_diagnostics(
+        &self,
+        uri: &Url,
+        kind: SymbolKind,
+        code: &str,
+        label: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let index = self.symbols.index(kind);
+        for entry in index.references_iter() {
+            let id = entry.key();
+            let references: Vec<&SymbolReference> = entry
+                .value()
+                .iter()
+                .filter(|reference| reference.location.uri == *uri)
+                .collect();
+            if references.is_empty() || index.has_definition(id) {
+                continue;
+            }
+            // Room lists may name a set of rooms instead.
+            if kind == SymbolKind::Room && self.symbols.sets.has_definition(id) {
+                continue;
+            }
 
-        for uri in uris {
-            self.check_diagnostics(&uri).await;
+            let suggestions = suggest_similar_ids(
+                id,
+                index
+                    .definitions_iter()
+                    .map(|definition| definition.key().clone()),
+            );
+            for reference in references {
+                diagnostics.push(Diagnostic {
+                    range: reference.location.range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: rule_code(code),
+                    code_description: None,
+                    source: Some("amble-lsp".to_string()),
+                    message: format!(
+                        "Undefined {}: '{}'{}",
+                        label,
+                        reference.raw_id,
+                        did_you_mean(&suggestions)
+                    ),
+                    related_information: None,
+                    tags: None,
+                    data: undefined_symbol_data(
+                        kind,
+                        id,
+                        &suggestions,
+                        reference.location.rename_range(),
+                    ),
+                });
+            }
+        }
+    }
+
+    /// Reports tree-sitter parse errors; cascades inside one definition share a diagnostic.
+    fn append_syntax_diagnostics(&self, uri: &Url, diagnostics: &mut Vec<Diagnostic>) {
+        let Some(errors) = self.syntax_errors.get(&uri.to_string()) else {
+            return;
+        };
+
+        for error in errors.iter() {
+            let related_information = if error.related.is_empty() {
+                None
+            } else {
+                Some(
+                    error
+                        .related
+                        .iter()
+                        .map(|(range, message)| DiagnosticRelatedInformation {
+                            location: Location {
+                                uri: uri.clone(),
+                                range: *range,
+                            },
+                            message: message.clone(),
+                        })
+                        .collect(),
+                )
+            };
+
+            diagnostics.push(Diagnostic {
+                range: error.range,
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: rule_code("syntax-error"),
+                code_description: None,
+                source: Some("amble-lsp".to_string()),
+                message: format!("Syntax error: {}", error.message),
+                related_information,
+                tags: None,
+                data: None,
+            });
         }
     }
 
     /// Flags duplicate definitions, downgrading flags to hints because multiple triggers
-    /// may intentionally set the same game state.
+    /// may intentionally set the same game state, and trigger names to warnings.
     fn append_duplicate_definition_diagnostics(
         &self,
         uri: &Url,
         diagnostics: &mut Vec<Diagnostic>,
     ) {
-        self.append_duplicate_diagnostics_for_index(
-            uri,
-            diagnostics,
-            SymbolKind::Room,
-            &self.symbols.rooms,
-        );
-        self.append_duplicate_diagnostics_for_index(
-            uri,
-            diagnostics,
-            SymbolKind::Item,
-            &self.symbols.items,
-        );
-        self.append_duplicate_diagnostics_for_index(
-            uri,
-            diagnostics,
-            SymbolKind::Npc,
-            &self.symbols.npcs,
-        );
+        for (kind, index) in [
+            (SymbolKind::Room, &self.symbols.rooms),
+            (SymbolKind::Item, &self.symbols.items),
+            (SymbolKind::Npc, &self.symbols.npcs),
+            (SymbolKind::Set, &self.symbols.sets),
+            (SymbolKind::Cond, &self.symbols.conds),
+            (SymbolKind::ActionSet, &self.symbols.action_sets),
+            (SymbolKind::Spinner, &self.symbols.spinners),
+            (SymbolKind::Goal, &self.symbols.goals),
+        ] {
+            self.append_duplicate_diagnostics_for_index(
+                uri,
+                diagnostics,
+                index,
+                DiagnosticSeverity::ERROR,
+                "duplicate-definition",
+                |id, _| format!("Duplicate {} definition: '{}'", kind.label(), id),
+            );
+        }
         self.append_duplicate_flag_diagnostics(uri, diagnostics);
+        // The engine uses a trigger's name as its identity, so collisions are worth a warning.
         self.append_duplicate_diagnostics_for_index(
             uri,
             diagnostics,
-            SymbolKind::Set,
-            &self.symbols.sets,
-        );
-        self.append_duplicate_diagnostics_for_index(
-            uri,
-            diagnostics,
-            SymbolKind::Cond,
-            &self.symbols.conds,
-        );
-        self.append_duplicate_diagnostics_for_index(
-            uri,
-            diagnostics,
-            SymbolKind::ActionSet,
-            &self.symbols.action_sets,
+            &self.symbols.triggers,
+            DiagnosticSeverity::WARNING,
+            "duplicate-trigger-name",
+            |id, count| {
+                format!(
+                    "Trigger name '{}' is used by {} triggers; names must be unique",
+                    id, count
+                )
+            },
         );
     }
 
+    /// Reports every definition in `uri` of an id defined more than once in `index`, pointing
+    /// at the other definitions. `message` gets the id and how many definitions share it.
     fn append_duplicate_diagnostics_for_index(
         &self,
         uri: &Url,
         diagnostics: &mut Vec<Diagnostic>,
-        kind: SymbolKind,
         index: &SymbolIndex,
+        severity: DiagnosticSeverity,
+        code: &str,
+        message: impl Fn(&str, usize) -> String,
     ) {
         for entry in index.duplicate_definitions_iter() {
             let id = entry.key().clone();
@@ -1040,20 +1788,33 @@ impl Backend {
             }
             definitions.extend(duplicates);
 
-            for def in definitions {
-                if def.location.uri == *uri {
-                    diagnostics.push(Diagnostic {
-                        range: def.location.range,
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        code: None,
-                        code_description: None,
-                        source: Some("amble-lsp".to_string()),
-                        message: format!("Duplicate {} definition: '{}'", kind.label(), id),
-                        related_information: None,
-                        tags: None,
-                        data: None,
-                    });
-                }
+            for def in definitions.iter().filter(|def| def.location.uri == *uri) {
+                let related_information = definitions
+                    .iter()
+                    .filter(|other| {
+                        other.location.uri != def.location.uri
+                            || other.location.range != def.location.range
+                    })
+                    .map(|other| DiagnosticRelatedInformation {
+                        location: Location {
+                            uri: other.location.uri.clone(),
+                            range: other.location.range,
+                        },
+                        message: "Also defined here".to_string(),
+                    })
+                    .collect();
+
+                diagnostics.push(Diagnostic {
+                    range: def.location.range,
+                    severity: Some(severity),
+                    code: rule_code(code),
+                    code_description: None,
+                    source: Some("amble-lsp".to_string()),
+                    message: message(&id, definitions.len()),
+                    related_information: Some(related_information),
+                    tags: None,
+                    data: None,
+                });
             }
         }
     }
@@ -1077,7 +1838,7 @@ impl Backend {
                     diagnostics.push(Diagnostic {
                         range: def.location.range,
                         severity: Some(DiagnosticSeverity::HINT),
-                        code: None,
+                        code: rule_code("flag-set-in-multiple-triggers"),
                         code_description: None,
                         source: Some("amble-lsp".to_string()),
                         message: format!(
@@ -1106,6 +1867,12 @@ impl Backend {
             SymbolKind::ActionSet,
             &self.symbols.action_sets,
         );
+        self.append_unused_for_index(
+            uri,
+            diagnostics,
+            SymbolKind::Spinner,
+            &self.symbols.spinners,
+        );
     }
 
     fn append_unused_for_index(
@@ -1123,6 +1890,9 @@ impl Backend {
             if definition.location.uri != *uri {
                 continue;
             }
+            if kind == SymbolKind::Spinner && BUILTIN_SPINNERS.contains(&id.as_str()) {
+                continue;
+            }
 
             let has_references = {
                 if let Some(refs) = index.references(&id) {
@@ -1138,7 +1908,7 @@ impl Backend {
                 diagnostics.push(Diagnostic {
                     range: definition.location.range,
                     severity: Some(DiagnosticSeverity::HINT),
-                    code: None,
+                    code: rule_code("unused-definition"),
                     code_description: None,
                     source: Some("amble-lsp".to_string()),
                     message: format!("{} '{}' is never referenced", kind.label(), id),
@@ -1175,7 +1945,7 @@ impl Backend {
                 diagnostics.push(Diagnostic {
                     range: definition.location.range,
                     severity: Some(DiagnosticSeverity::WARNING),
-                    code: None,
+                    code: rule_code("missing-metadata"),
                     code_description: None,
                     source: Some("amble-lsp".to_string()),
                     message,
@@ -1202,7 +1972,7 @@ impl Backend {
                     end: Position::default(),
                 },
                 severity: Some(DiagnosticSeverity::WARNING),
-                code: None,
+                code: rule_code("missing-player-start"),
                 code_description: None,
                 source: Some("amble-lsp".to_string()),
                 message: "No player start room defined in this workspace".to_string(),
@@ -1222,7 +1992,7 @@ impl Backend {
                 diagnostics.push(Diagnostic {
                     range: start.range.clone(),
                     severity: Some(DiagnosticSeverity::WARNING),
-                    code: None,
+                    code: rule_code("multiple-player-starts"),
                     code_description: None,
                     source: Some("amble-lsp".to_string()),
                     message: format!(
@@ -1237,6 +2007,103 @@ impl Backend {
         }
     }
 
+    /// Rooms some chain of exits, revealed exits, exit patches or pushes reaches from the
+    /// player start; `None` without a valid start. Walks every document's edges, so build it
+    /// once per batch of documents.
+    pub(crate) fn reachable_rooms(&self) -> Option<HashSet<String>> {
+        let starts: Vec<String> = self
+            .player_starts
+            .iter()
+            .flat_map(|entry| {
+                entry
+                    .value()
+                    .iter()
+                    .map(|start| start.room_id.clone())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|room_id| self.symbols.rooms.has_definition(room_id))
+            .collect();
+        if starts.is_empty() {
+            return None;
+        }
+
+        let edges: Vec<RoomEdge> = self
+            .room_edges
+            .iter()
+            .flat_map(|entry| entry.value().clone())
+            .collect();
+        Some(reachable_rooms(&starts, &edges))
+    }
+
+    /// Flags rooms in `uri` missing from `reachable`, the workspace's reachable rooms.
+    fn append_reachability_diagnostics(
+        &self,
+        uri: &Url,
+        reachable: Option<&HashSet<String>>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        // Without a valid start every room would be flagged; the start diagnostics cover that.
+        let Some(reachable) = reachable else {
+            return;
+        };
+
+        for entry in self.symbols.rooms.definitions_iter() {
+            if reachable.contains(entry.key()) {
+                continue;
+            }
+            let definition = entry.value();
+            if definition.location.uri != *uri {
+                continue;
+            }
+            diagnostics.push(Diagnostic {
+                range: definition.location.range,
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: rule_code("unreachable-room"),
+                code_description: None,
+                source: Some("amble-lsp".to_string()),
+                message: format!(
+                    "Room '{}' can never be entered from the player start",
+                    entry.key()
+                ),
+                related_information: None,
+                tags: None,
+                data: None,
+            });
+        }
+    }
+
+    /// Warns when a trigger moves an NPC into a state that has no dialogue block.
+    fn append_npc_state_diagnostics(&self, uri: &Url, diagnostics: &mut Vec<Diagnostic>) {
+        let Some(changes) = self.npc_state_changes.get(&uri.to_string()) else {
+            return;
+        };
+
+        for change in changes.iter() {
+            if !self.symbols.npcs.has_definition(&change.npc_id) {
+                continue;
+            }
+            let id = npc_state_id(&change.npc_id, &change.state);
+            if self.symbols.npc_states.has_definition(&id) {
+                continue;
+            }
+
+            diagnostics.push(Diagnostic {
+                range: change.range,
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: rule_code("missing-npc-dialogue"),
+                code_description: None,
+                source: Some("amble-lsp".to_string()),
+                message: format!(
+                    "NPC '{}' has no dialogue for state '{}'",
+                    change.npc_id, change.state
+                ),
+                related_information: None,
+                tags: None,
+                data: None,
+            });
+        }
+    }
+
     /// Validates that sequence-style flag references stay within bounds and avoids referencing
     /// non-sequence flags with a `#N` suffix.
     fn append_flag_sequence_diagnostics(&self, uri: &Url, diagnostics: &mut Vec<Diagnostic>) {
@@ -1261,7 +2128,7 @@ impl Backend {
                                 diagnostics.push(Diagnostic {
                                     range: reference.location.range,
                                     severity: Some(DiagnosticSeverity::WARNING),
-                                    code: None,
+                                    code: rule_code("flag-sequence-out-of-range"),
                                     code_description: None,
                                     source: Some("amble-lsp".to_string()),
                                     message: format!(
@@ -1277,7 +2144,7 @@ impl Backend {
                             diagnostics.push(Diagnostic {
                                 range: reference.location.range,
                                 severity: Some(DiagnosticSeverity::WARNING),
-                                code: None,
+                                code: rule_code("flag-not-a-sequence"),
                                 code_description: None,
                                 source: Some("amble-lsp".to_string()),
                                 message: format!(
@@ -1297,6 +2164,11 @@ impl Backend {
     }
 }
 
+/// Stable identifier for a diagnostic's rule, used by `amble-lsp check` reports.
+fn rule_code(code: &str) -> Option<NumberOrString> {
+    Some(NumberOrString::String(code.to_string()))
+}
+
 fn metadata_issues_for_definition(id: &str, def: &SymbolDefinition) -> Vec<String> {
     match &def.metadata {
         SymbolMetadata::Room(meta) => {
@@ -1340,17 +2212,34 @@ fn text_missing(value: &Option<String>) -> bool {
         .unwrap_or(true)
 }
 
+/// Every `.amble` file under `dir`, skipping `IGNORED_DIRECTORIES`.
+pub(crate) fn amble_files_under(dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|entry| should_visit_entry(entry))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("amble"))
+        .collect()
+}
+
 fn should_visit_entry(entry: &DirEntry) -> bool {
     if entry.file_type().is_dir() {
         if let Some(name) = entry.file_name().to_str() {
-            return !IGNORED_DIRECTORIES
-                .iter()
-                .any(|ignored| ignored.eq_ignore_ascii_case(name));
+            return !is_ignored_directory(name);
         }
     }
     true
 }
 
+fn is_ignored_directory(name: &str) -> bool {
+    IGNORED_DIRECTORIES
+        .iter()
+        .any(|ignored| ignored.eq_ignore_ascii_case(name))
+}
+
 fn file_modified(path: &Path) -> Option<SystemTime> {
     std::fs::metadata(path).ok()?.modified().ok()
 }
@@ -1379,6 +2268,11 @@ pub(crate) fn format_hover(
         SymbolMetadata::Set(meta) => format_set_hover(id, meta, relative_path),
         SymbolMetadata::Cond(meta) => format_cond_hover(id, meta, relative_path),
         SymbolMetadata::ActionSet(meta) => format_action_set_hover(id, meta, relative_path),
+        SymbolMetadata::Spinner(meta) => format_spinner_hover(id, meta, relative_path),
+        SymbolMetadata::Goal(meta) => format_goal_hover(id, meta, relative_path),
+        SymbolMetadata::Trigger(meta) => format_trigger_hover(id, meta, relative_path),
+        SymbolMetadata::NpcState(meta) => format_npc_state_hover(meta, relative_path),
+        SymbolMetadata::ItemAbility(meta) => format_item_ability_hover(meta, relative_path),
     }
 }
 
@@ -1398,7 +2292,7 @@ fn format_room_hover(id: &str, meta: &RoomMetadata, relative_path: Option<&str>)
         } else {
             meta.exits
                 .iter()
-                .map(|exit| sanitize_markdown(exit))
+                .map(|exit| sanitize_markdown(&exit.to))
                 .collect::<Vec<_>>()
                 .join(", ")
         }
@@ -1475,6 +2369,52 @@ fn format_npc_hover(id: &str, meta: &NpcMetadata, relative_path: Option<&str>) -
             .map(sanitize_markdown)
             .unwrap_or_else(|| "(none)".to_string())
     ));
+    lines.push(format!(
+        "- **Dialogue states:** {}",
+        if meta.dialogue_states.is_empty() {
+            "(none)".to_string()
+        } else {
+            meta.dialogue_states
+                .iter()
+                .map(|state| sanitize_markdown(state))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    ));
+    lines.join("\n")
+}
+
+fn format_npc_state_hover(meta: &NpcStateMetadata, relative_path: Option<&str>) -> String {
+    let state_label = if meta.custom {
+        format!("custom {}", meta.state)
+    } else {
+        meta.state.clone()
+    };
+    let mut lines = vec![entity_title_line("NPC STATE", None, &state_label)];
+    if let Some(location_line) = definition_path_line(relative_path) {
+        lines.push(location_line);
+    }
+    lines.push(format!("- **NPC:** {}", sanitize_markdown(&meta.npc_id)));
+    lines.push(format!("- **Dialogue lines:** {}", meta.line_count));
+    lines.join("\n")
+}
+
+fn format_item_ability_hover(meta: &ItemAbilityMetadata, relative_path: Option<&str>) -> String {
+    let mut lines = vec![entity_title_line(
+        "ABILITY",
+        Some(&meta.ability),
+        &meta.item_id,
+    )];
+    if let Some(location_line) = definition_path_line(relative_path) {
+        lines.push(location_line);
+    }
+    lines.push(format!("- **Item:** {}", sanitize_markdown(&meta.item_id)));
+    if let Some(target) = &meta.target {
+        lines.push(format!("- **Target:** {}", sanitize_markdown(target)));
+    }
+    if meta.added_by_patch {
+        lines.push("- **Granted by:** `modify item` patch".to_string());
+    }
     lines.join("\n")
 }
 
@@ -1554,6 +2494,62 @@ fn format_action_set_hover(
     lines.join("\n")
 }
 
+fn format_spinner_hover(id: &str, meta: &SpinnerMetadata, relative_path: Option<&str>) -> String {
+    let mut lines = vec![entity_title_line("SPINNER", None, id)];
+    if let Some(location_line) = definition_path_line(relative_path) {
+        lines.push(location_line);
+    }
+    lines.push(format!("- **Wedges:** {}", meta.wedge_count));
+    lines.push(format!("- **Total width:** {}", meta.total_width));
+    lines.join("\n")
+}
+
+fn format_goal_hover(id: &str, meta: &GoalMetadata, relative_path: Option<&str>) -> String {
+    let mut lines = vec![entity_title_line("GOAL", meta.name.as_deref(), id)];
+    if let Some(location_line) = definition_path_line(relative_path) {
+        lines.push(location_line);
+    }
+    lines.push(format!(
+        "- **Description:** {}",
+        truncate_description(meta.description.as_deref())
+    ));
+    lines.push(format!(
+        "- **Group:** {}",
+        meta.group
+            .as_deref()
+            .map(sanitize_markdown)
+            .unwrap_or_else(|| "(none)".to_string())
+    ));
+    for (label, condition) in [
+        ("Starts when", &meta.start_condition),
+        ("Done when", &meta.done_condition),
+        ("Fails when", &meta.fail_condition),
+    ] {
+        if let Some(condition) = condition {
+            lines.push(format!("- **{}:** {}", label, sanitize_markdown(condition)));
+        }
+    }
+    lines.join("\n")
+}
+
+fn format_trigger_hover(id: &str, meta: &TriggerMetadata, relative_path: Option<&str>) -> String {
+    let mut lines = vec![entity_title_line("TRIGGER", None, id)];
+    if let Some(location_line) = definition_path_line(relative_path) {
+        lines.push(location_line);
+    }
+    lines.push(format!(
+        "- **When:** {}",
+        meta.event
+            .as_deref()
+            .map(sanitize_markdown)
+            .unwrap_or_else(|| "(missing)".to_string())
+    ));
+    if meta.only_once {
+        lines.push("- **Fires:** only once".to_string());
+    }
+    lines.join("\n")
+}
+
 fn definition_path_line(relative_path: Option<&str>) -> Option<String> {
     relative_path.map(|path| {
         let shortened = shorten_to_data_root(path);
@@ -1640,7 +2636,7 @@ fn truncate_string(value: String, max_chars: usize) -> String {
     }
 }
 
-fn range_from_node(document: &Document, node: &Node) -> Range {
+pub(crate) fn range_from_node(document: &Document, node: &Node) -> Range {
     Range {
         start: document.position_at(node.start_byte()),
         end: document.position_at(node.end_byte()),
@@ -1728,7 +2724,7 @@ fn normalize_string_literal(literal: &str) -> String {
 fn extract_room_metadata(
     room_node: &Node,
     text: &str,
-) -> (Option<String>, Option<String>, Vec<String>) {
+) -> (Option<String>, Option<String>, Vec<RoomExit>) {
     let mut name = None;
     let mut description = None;
     let mut exits = Vec::new();
@@ -1750,8 +2746,8 @@ fn extract_room_metadata(
                     }
                 }
                 "room_exit" => {
-                    if let Some(dest) = child.child_by_field_name("dest") {
-                        exits.push(slice_text(text, &dest).trim().to_string());
+                    if let Some(exit) = extract_room_exit(&child, text) {
+                        exits.push(exit);
                     }
                 }
                 _ => {}
@@ -1762,6 +2758,63 @@ fn extract_room_metadata(
     (name, description, exits)
 }
 
+fn extract_room_exit(exit_node: &Node, text: &str) -> Option<RoomExit> {
+    let dest = exit_node.child_by_field_name("dest")?;
+    let mut exit = RoomExit {
+        direction: exit_node
+            .child_by_field_name("dir")
+            .map(|dir| normalize_string_literal(slice_text(text, &dir).trim()))
+            .unwrap_or_default(),
+        to: slice_text(text, &dest).trim().to_string(),
+        ..RoomExit::default()
+    };
+
+    let Some(block) = named_child_by_kind(exit_node, "exit_block") else {
+        return Some(exit);
+    };
+    let mut cursor = block.walk();
+    for option in block.named_children(&mut cursor) {
+        let mut option_cursor = option.walk();
+        let Some(detail) = option.named_children(&mut option_cursor).next() else {
+            // `hidden` and `locked` are bare keywords with no child nodes.
+            match slice_text(text, &option).trim().trim_end_matches(',') {
+                "hidden" => exit.hidden = true,
+                "locked" => exit.locked = true,
+                _ => {}
+            }
+            continue;
+        };
+        let mut detail_cursor = detail.walk();
+        match detail.kind() {
+            "required_flags_stmt" => {
+                for flag_req in detail.named_children(&mut detail_cursor) {
+                    let flag =
+                        named_child_by_field_name(&flag_req, "flag_name").unwrap_or(flag_req);
+                    exit.required_flags
+                        .push(slice_text(text, &flag).trim().to_string());
+                }
+            }
+            "required_items_stmt" => {
+                for item in detail.named_children(&mut detail_cursor) {
+                    exit.required_items
+                        .push(slice_text(text, &item).trim().to_string());
+                }
+            }
+            "barred_stmt" => {
+                exit.barred = Some(
+                    detail
+                        .child_by_field_name("msg")
+                        .map(|msg| normalize_string_literal(slice_text(text, &msg).trim()))
+                        .unwrap_or_default(),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    Some(exit)
+}
+
 fn format_location_node(location_node: &Node, text: &str) -> String {
     if let Some(room) = named_child_by_kind(location_node, "room_id")
         .or_else(|| named_child_by_kind(location_node, "_room_ref"))
@@ -1973,6 +3026,86 @@ fn extract_npc_metadata(
     (name, description, location, state)
 }
 
+/// Lists the states an NPC has dialogue for, in declaration order.
+fn extract_npc_dialogue_states(npc_node: &Node, text: &str) -> Vec<String> {
+    let Some(block) = named_child_by_kind(npc_node, "npc_block") else {
+        return Vec::new();
+    };
+
+    let mut states = Vec::new();
+    let mut cursor = block.walk();
+    for child in block.named_children(&mut cursor) {
+        if child.kind() != "npc_dialogue_block" {
+            continue;
+        }
+        if let Some(token) = named_child_by_kind(&child, "npc_state").and_then(npc_state_token) {
+            states.push(npc_state_name(slice_text(text, &token)));
+        }
+    }
+    states
+}
+
+/// Drills into the various state spellings (`happy`, `custom sad`, `custom(sad)`,
+/// `custom "sad"`) and returns the node holding the bare state name.
+fn npc_state_token(node: Node) -> Option<Node> {
+    match node.kind() {
+        "identifier" | "custom_state" | "string" => Some(node),
+        _ => node
+            .child_by_field_name("custom_state")
+            .or_else(|| node.child_by_field_name("state"))
+            .or_else(|| node.named_child(0))
+            .and_then(npc_state_token),
+    }
+}
+
+/// Normalizes a state token, unquoting `custom "x"` and dropping the legacy `custom:` prefix.
+fn npc_state_name(raw: &str) -> String {
+    let state = normalize_string_literal(raw.trim());
+    match state.strip_prefix("custom:") {
+        Some(custom) => custom.trim().to_string(),
+        None => state,
+    }
+}
+
+/// Finds the NPC a state belongs to by walking up to the nearest construct naming an NPC.
+fn enclosing_npc_id(node: Node, text: &str) -> Option<String> {
+    let mut current = Some(node);
+    while let Some(candidate) = current {
+        if matches!(
+            candidate.kind(),
+            "npc_def"
+                | "action_set_npc_state"
+                | "cond_npc_in_state"
+                | "ovl_npc_state"
+                | "ovl_npc_state_set"
+                | "action_modify_npc"
+        ) {
+            let npc_node = candidate.child_by_field_name("npc_id")?;
+            let npc_id = slice_text(text, &npc_node).trim();
+            return (!npc_id.is_empty()).then(|| npc_id.to_string());
+        }
+        current = candidate.parent();
+    }
+    None
+}
+
+/// Finds the item an ability belongs to: the item being defined, used, or patched.
+fn enclosing_item_id(node: Node, text: &str) -> Option<String> {
+    let mut current = Some(node);
+    while let Some(candidate) = current {
+        if matches!(
+            candidate.kind(),
+            "item_def" | "use_item" | "action_modify_item"
+        ) {
+            let item_node = candidate.child_by_field_name("item_id")?;
+            let item_id = slice_text(text, &item_node).trim();
+            return (!item_id.is_empty()).then(|| item_id.to_string());
+        }
+        current = candidate.parent();
+    }
+    None
+}
+
 fn find_trigger_name(node: Node, text: &str) -> Option<String> {
     let mut current = node;
     while let Some(parent) = current.parent() {
@@ -2030,53 +3163,420 @@ fn extract_set_rooms(set_node: &Node, text: &str) -> Vec<String> {
     }
 }
 
-fn extract_cond_expression(cond_node: &Node, text: &str) -> String {
-    if let Some(expr_node) = named_child_by_kind(cond_node, "trigger_cond") {
-        return slice_text(text, &expr_node).trim().to_string();
+fn extract_cond_expression(cond_node: &Node, text: &str) -> String {
+    if let Some(expr_node) = named_child_by_kind(cond_node, "trigger_cond") {
+        return slice_text(text, &expr_node).trim().to_string();
+    }
+    if let Some(expr_node) = named_child_by_field_name(cond_node, "condition") {
+        return slice_text(text, &expr_node).trim().to_string();
+    }
+    String::new()
+}
+
+fn extract_action_set_body(action_set_node: &Node, text: &str) -> String {
+    if let Some(body_node) = named_child_by_field_name(action_set_node, "body") {
+        return slice_text(text, &body_node).trim().to_string();
+    }
+    String::new()
+}
+
+/// Counts the wedges in a spinner block and sums their widths (a wedge without `width` counts as 1).
+fn extract_spinner_metadata(spinner_node: &Node, text: &str) -> (usize, i64) {
+    let Some(block) = named_child_by_kind(spinner_node, "spinner_block") else {
+        return (0, 0);
+    };
+
+    let mut wedge_count = 0;
+    let mut total_width = 0;
+    let mut cursor = block.walk();
+    for stmt in block.named_children(&mut cursor) {
+        if stmt.kind() != "spinner_stmt" {
+            continue;
+        }
+        wedge_count += 1;
+        total_width += stmt
+            .child_by_field_name("width")
+            .and_then(|width| slice_text(text, &width).trim().parse::<i64>().ok())
+            .unwrap_or(1);
+    }
+
+    (wedge_count, total_width)
+}
+
+fn extract_goal_metadata(document: &Document, goal_node: &Node, text: &str) -> GoalMetadata {
+    let mut meta = GoalMetadata::default();
+    let Some(block) = named_child_by_kind(goal_node, "goal_block") else {
+        return meta;
+    };
+
+    let condition_text = |stmt: &Node, field: &str| {
+        stmt.child_by_field_name(field)
+            .map(|node| slice_text(text, &node).trim().to_string())
+    };
+
+    let mut cursor = block.walk();
+    for child in block.named_children(&mut cursor) {
+        match child.kind() {
+            "goal_name_stmt" => {
+                if let Some(name_node) = child.child_by_field_name("goal_name") {
+                    meta.name = Some(normalize_string_literal(slice_text(text, &name_node)));
+                }
+            }
+            "goal_desc_stmt" => {
+                if let Some(desc_node) = child.child_by_field_name("goal_description") {
+                    meta.description = Some(normalize_string_literal(slice_text(text, &desc_node)));
+                }
+            }
+            "goal_group_stmt" => {
+                if let Some(group_node) = child.child_by_field_name("goal_group") {
+                    meta.group = Some(slice_text(text, &group_node).trim().to_string());
+                }
+            }
+            "goal_start_stmt" => {
+                meta.start_condition = condition_text(&child, "start_condition");
+                meta.start = goal_condition(document, &child, "start_condition", text);
+            }
+            "goal_done_stmt" => {
+                meta.done_condition = condition_text(&child, "done_condition");
+                meta.done = goal_condition(document, &child, "done_condition", text);
+            }
+            "goal_fail_stmt" => {
+                meta.fail_condition = condition_text(&child, "fail_condition");
+                meta.fail = goal_condition(document, &child, "fail_condition", text);
+            }
+            _ => {}
+        }
+    }
+
+    meta
+}
+
+fn goal_condition(
+    document: &Document,
+    stmt: &Node,
+    field: &str,
+    text: &str,
+) -> Option<GoalCondition> {
+    let condition = stmt.child_by_field_name(field)?;
+    let kind = match condition.kind() {
+        "gc_has_flag" => GoalConditionKind::HasFlag,
+        "gc_missing_flag" => GoalConditionKind::MissingFlag,
+        "gc_has_item" => GoalConditionKind::HasItem,
+        "gc_reached_room" => GoalConditionKind::ReachedRoom,
+        "gc_goal_complete" => GoalConditionKind::GoalComplete,
+        "gc_flag_progress" => GoalConditionKind::FlagInProgress,
+        "gc_flag_complete" => GoalConditionKind::FlagComplete,
+        _ => return None,
+    };
+    let mut cursor = condition.walk();
+    let id_node = condition.named_children(&mut cursor).next()?;
+    let raw_id = slice_text(text, &id_node).trim();
+    if raw_id.is_empty() {
+        return None;
+    }
+
+    let range = range_from_node(document, &id_node);
+    let id = match kind {
+        GoalConditionKind::HasFlag
+        | GoalConditionKind::MissingFlag
+        | GoalConditionKind::FlagInProgress
+        | GoalConditionKind::FlagComplete => normalize_flag_reference(raw_id, &range).0,
+        _ => raw_id.to_string(),
+    };
+    Some(GoalCondition { kind, id, range })
+}
+
+fn extract_trigger_metadata(trigger_node: &Node, text: &str) -> TriggerMetadata {
+    let event = named_child_by_kind(trigger_node, "when_cond").map(|when_node| {
+        slice_text(text, &when_node)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    let only_once = trigger_node.child_by_field_name("once").is_some();
+
+    TriggerMetadata { event, only_once }
+}
+
+/// Narrows a quoted string node to the text between its quotes, for renames.
+fn string_contents_range(document: &Document, node: &Node, text: &str) -> Option<Range> {
+    let raw = slice_text(text, node);
+    if raw.len() < 2 || !raw.starts_with('"') || !raw.ends_with('"') || raw.starts_with("\"\"\"") {
+        return None;
+    }
+    Some(Range {
+        start: document.position_at(node.start_byte() + 1),
+        end: document.position_at(node.end_byte() - 1),
+    })
+}
+
+/// Walks the syntax tree and records every `player_start room ...` statement for diagnostics.
+fn collect_player_starts(
+    document: &Document,
+    root: Node,
+    text: &str,
+    uri: &Url,
+) -> Vec<PlayerStart> {
+    let mut result = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "player_start" {
+            if let Some(room_node) = node.child_by_field_name("room_id") {
+                let room_id = slice_text(text, &room_node).trim().to_string();
+                if !room_id.is_empty() {
+                    let range = range_from_node(document, &room_node);
+                    result.push(PlayerStart {
+                        room_id,
+                        range,
+                        uri: uri.clone(),
+                    });
+                }
+            }
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+
+    result
+}
+
+/// Collects every way the player can move between rooms: room exits, `reveal exit`,
+/// `modify room { add exit }` and `push player to`.
+fn collect_room_edges(root: Node, text: &str) -> Vec<RoomEdge> {
+    let field_text = |node: Node, field: &str| {
+        node.child_by_field_name(field)
+            .map(|child| slice_text(text, &child).trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let mut result = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let edge = match node.kind() {
+            "room_exit" => field_text(node, "dest").map(|to| RoomEdge {
+                from: enclosing_room_id(node, text, "room_def"),
+                to,
+            }),
+            "room_patch_add_exit" => field_text(node, "destination").map(|to| RoomEdge {
+                from: enclosing_room_id(node, text, "action_modify_room"),
+                to,
+            }),
+            "action_reveal_exit" => field_text(node, "to_room").map(|to| RoomEdge {
+                from: field_text(node, "from_room"),
+                to,
+            }),
+            "action_push_player" => {
+                field_text(node, "room_id").map(|to| RoomEdge { from: None, to })
+            }
+            _ => None,
+        };
+        if let Some(edge) = edge {
+            result.push(edge);
+        }
+
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+
+    result
+}
+
+fn collect_flag_uses(document: &Document, root: Node, text: &str) -> Vec<FlagUse> {
+    let mut result = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+
+        // Flag names wrap an inner `flag_name`; only the outer one is a use.
+        if node.kind() != "flag_name" {
+            continue;
+        }
+        let Some(parent) = node.parent() else {
+            continue;
+        };
+        let kind = match parent.kind() {
+            "flag_name" => continue,
+            "action_add_flag" | "action_add_seq" | "action_advance_flag" => FlagUseKind::Set,
+            "action_remove_flag" | "action_reset_flag" => FlagUseKind::Remove,
+            _ => FlagUseKind::Read,
+        };
+        let Some(user) = flag_user(node, text) else {
+            continue;
+        };
+        let name = slice_text(text, &node).trim();
+        if name.is_empty() {
+            continue;
+        }
+
+        let range = range_from_node(document, &node);
+        let (flag, _) = normalize_flag_reference(name, &range);
+        result.push(FlagUse {
+            flag,
+            kind,
+            user,
+            range,
+        });
+    }
+
+    result
+}
+
+/// The nearest trigger, action set, condition alias, goal, exit or room around `node`.
+fn flag_user(node: Node, text: &str) -> Option<FlagUser> {
+    let field_text = |node: Node, field: &str| {
+        node.child_by_field_name(field)
+            .map(|child| slice_text(text, &child).trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        let user = match candidate.kind() {
+            "trigger_def" => field_text(candidate, "name")
+                .map(|name| FlagUser::Trigger(normalize_string_literal(&name))),
+            "action_set_decl" => field_text(candidate, "name").map(FlagUser::ActionSet),
+            "cond_decl" => field_text(candidate, "name").map(FlagUser::Condition),
+            "goal_def" => field_text(candidate, "goal_id").map(FlagUser::Goal),
+            "room_exit" => Some(FlagUser::Exit {
+                room: enclosing_room_id(candidate, text, "room_def")?,
+                direction: field_text(candidate, "dir")
+                    .map(|dir| normalize_string_literal(&dir))
+                    .unwrap_or_default(),
+            }),
+            "room_def" => field_text(candidate, "room_id").map(FlagUser::Room),
+            _ => None,
+        };
+        if user.is_some() {
+            return user;
+        }
+        current = candidate.parent();
+    }
+    None
+}
+
+fn enclosing_room_id(node: Node, text: &str, owner_kind: &str) -> Option<String> {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if candidate.kind() == owner_kind {
+            let room_node = candidate.child_by_field_name("room_id")?;
+            let room_id = slice_text(text, &room_node).trim();
+            return (!room_id.is_empty()).then(|| room_id.to_string());
+        }
+        current = candidate.parent();
     }
-    if let Some(expr_node) = named_child_by_field_name(cond_node, "condition") {
-        return slice_text(text, &expr_node).trim().to_string();
+    None
+}
+
+/// Warns about goals that wait on themselves through `goal complete`, and required goals
+/// whose `done` flag no trigger ever sets.
+fn append_goal_diagnostics(graph: &GoalGraph, uri: &Url, diagnostics: &mut Vec<Diagnostic>) {
+    for goal in graph.goals.iter().filter(|goal| goal.uri == *uri) {
+        if let Some(cycle) = graph.cycle_through(&goal.id) {
+            let prerequisite = cycle.get(1).unwrap_or(&goal.id);
+            if let Some(condition) = goal
+                .prerequisites()
+                .find(|condition| condition.id == *prerequisite)
+            {
+                let path: Vec<&str> = cycle
+                    .iter()
+                    .chain(std::iter::once(&goal.id))
+                    .map(String::as_str)
+                    .collect();
+                diagnostics.push(Diagnostic {
+                    range: condition.range,
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    code: rule_code("goal-dependency-cycle"),
+                    code_description: None,
+                    source: Some("amble-lsp".to_string()),
+                    message: format!(
+                        "Goal '{}' depends on itself: {}",
+                        goal.id,
+                        path.join(" -> ")
+                    ),
+                    related_information: None,
+                    tags: None,
+                    data: None,
+                });
+            }
+        }
+
+        let Some(done) = goal.done.as_ref() else {
+            continue;
+        };
+        let needs_flag = matches!(
+            done.kind,
+            GoalConditionKind::HasFlag | GoalConditionKind::FlagComplete
+        );
+        if !goal.is_required() || !needs_flag || graph.triggered_flags.contains(&done.id) {
+            continue;
+        }
+        diagnostics.push(Diagnostic {
+            range: goal.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: rule_code("goal-flag-never-set"),
+            code_description: None,
+            source: Some("amble-lsp".to_string()),
+            message: format!(
+                "Required goal '{}' can never be completed: no trigger sets flag '{}'",
+                goal.id, done.id
+            ),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.clone(),
+                    range: done.range,
+                },
+                message: "Done condition".to_string(),
+            }]),
+            tags: None,
+            data: None,
+        });
     }
-    String::new()
 }
 
-fn extract_action_set_body(action_set_node: &Node, text: &str) -> String {
-    if let Some(body_node) = named_child_by_field_name(action_set_node, "body") {
-        return slice_text(text, &body_node).trim().to_string();
+/// Parses `document` with `parser`, reusing its edited tree; `false` if parsing gave up.
+fn parse_document(parser: &mut Parser, document: &mut Document) -> bool {
+    match parser.parse(document.text(), document.tree()) {
+        Some(tree) => {
+            document.set_tree(tree);
+            true
+        }
+        None => false,
     }
-    String::new()
 }
 
-/// Walks the syntax tree and records every `player_start room ...` statement for diagnostics.
-fn collect_player_starts(
-    document: &Document,
-    root: Node,
-    text: &str,
-    uri: &Url,
-) -> Vec<PlayerStart> {
-    let mut result = Vec::new();
-    let mut stack = vec![root];
-    while let Some(node) = stack.pop() {
-        if node.kind() == "player_start" {
-            if let Some(room_node) = node.child_by_field_name("room_id") {
-                let room_id = slice_text(text, &room_node).trim().to_string();
-                if !room_id.is_empty() {
-                    let range = range_from_node(document, &room_node);
-                    result.push(PlayerStart {
-                        room_id,
-                        range,
-                        uri: uri.clone(),
-                    });
+/// Breadth-first walk over `edges` from the start rooms. Pushes are treated as reachable from
+/// anywhere, since we can't tell where the player will be when their trigger fires.
+fn reachable_rooms(starts: &[String], edges: &[RoomEdge]) -> HashSet<String> {
+    let mut exits: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut reachable: HashSet<String> = starts.iter().cloned().collect();
+    let mut queue: Vec<String> = starts.to_vec();
+    for edge in edges {
+        match &edge.from {
+            Some(from) => exits.entry(from).or_default().push(&edge.to),
+            None => {
+                if reachable.insert(edge.to.clone()) {
+                    queue.push(edge.to.clone());
                 }
             }
         }
-        let mut cursor = node.walk();
-        for child in node.named_children(&mut cursor) {
-            stack.push(child);
+    }
+
+    while let Some(room_id) = queue.pop() {
+        for &to in exits.get(room_id.as_str()).into_iter().flatten() {
+            if reachable.insert(to.to_string()) {
+                queue.push(to.to_string());
+            }
         }
     }
 
-    result
+    reachable
 }
 
 /// Parses the numeric suffix of a flag reference like `quest#3`, returning the index if present.
@@ -2130,6 +3630,10 @@ fn symbol_kind_from_kind(kind: &str) -> Option<SymbolKind> {
         "set_name" | "_set_ref" => Some(SymbolKind::Set),
         "cond_name" | "_cond_ref" => Some(SymbolKind::Cond),
         "action_set_name" | "_action_set_ref" => Some(SymbolKind::ActionSet),
+        "spinner_id" | "_spinner_ref" => Some(SymbolKind::Spinner),
+        "goal_id" | "_goal_ref" => Some(SymbolKind::Goal),
+        "custom_state" => Some(SymbolKind::NpcState),
+        "item_ability" => Some(SymbolKind::ItemAbility),
         _ => None,
     }
 }
@@ -2143,6 +3647,10 @@ fn symbol_kind_from_field(field_name: &str) -> Option<SymbolKind> {
         "set_name" => Some(SymbolKind::Set),
         "cond_name" => Some(SymbolKind::Cond),
         "action_set_name" => Some(SymbolKind::ActionSet),
+        "spinner" => Some(SymbolKind::Spinner),
+        "goal_id" => Some(SymbolKind::Goal),
+        "state" | "custom_state" | "npc_state" => Some(SymbolKind::NpcState),
+        "ability" => Some(SymbolKind::ItemAbility),
         _ => None,
     }
 }
@@ -2159,6 +3667,13 @@ fn is_definition_node<'tree>(node: &Node<'tree>, symbol_type: SymbolKind) -> boo
             SymbolKind::Set => kind == "set_decl",
             SymbolKind::Cond => kind == "cond_decl",
             SymbolKind::ActionSet => kind == "action_set_decl",
+            SymbolKind::Spinner => kind == "spinner_def",
+            SymbolKind::Goal => kind == "goal_def",
+            SymbolKind::Trigger => kind == "trigger_def",
+            SymbolKind::NpcState => kind == "npc_dialogue_block",
+            SymbolKind::ItemAbility => {
+                kind == "item_ability_stmt" || kind == "item_patch_add_ability"
+            }
         };
 
         if is_definition {
@@ -2183,6 +3698,17 @@ fn is_definition_field(parent_kind: &str, field_name: &str, symbol_type: SymbolK
         SymbolKind::Set => parent_kind == "set_decl" && field_name == "name",
         SymbolKind::Cond => parent_kind == "cond_decl" && field_name == "name",
         SymbolKind::ActionSet => parent_kind == "action_set_decl" && field_name == "name",
+        SymbolKind::Spinner => parent_kind == "spinner_def" && field_name == "name",
+        SymbolKind::Goal => parent_kind == "goal_def" && field_name == "goal_id",
+        SymbolKind::Trigger => parent_kind == "trigger_def" && field_name == "name",
+        SymbolKind::NpcState => parent_kind == "npc_state",
+        SymbolKind::ItemAbility => {
+            field_name == "ability"
+                && matches!(
+                    parent_kind,
+                    "item_ability_stmt" | "item_patch_add_ability" | "item_patch_remove_ability"
+                )
+        }
     }
 }
 
@@ -2387,7 +3913,18 @@ mod tests {
         let meta = RoomMetadata {
             name: Some("Test Room".into()),
             description: Some("A description".into()),
-            exits: vec!["north-hall".into(), "south-porch".into()],
+            exits: vec![
+                RoomExit {
+                    direction: "north".into(),
+                    to: "north-hall".into(),
+                    ..RoomExit::default()
+                },
+                RoomExit {
+                    direction: "south".into(),
+                    to: "south-porch".into(),
+                    ..RoomExit::default()
+                },
+            ],
         };
 
         let hover = format_room_hover("test-room", &meta, Some("rooms/test-room.amble"));
@@ -2574,6 +4111,7 @@ mod tests {
                 description: Some("desc".into()),
                 location: None,
                 state: None,
+                dialogue_states: Vec::new(),
             }),
         };
         let issues = metadata_issues_for_definition("npc_a", &def);
@@ -2717,4 +4255,405 @@ trigger "hint" when always {
         }
         assert_eq!(refs, vec!["common_steps".to_string()]);
     }
+
+    #[test]
+    fn detects_spinner_reference_context_in_actions() {
+        let source = "spinner ambient {\n    wedge \"Wind\" width 2\n}\ntrigger \"hint\" when always {\n    do spinner message ambient\n}\n";
+        let position = position_for_token(source, 4, "ambient", 2);
+        let symbol = completion_at(source, position);
+        assert_eq!(symbol, Some(SymbolKind::Spinner));
+    }
+
+    #[test]
+    fn query_indexing_covers_spinner_definitions_and_references() {
+        let source = r#"spinner ambient {
+    wedge "Wind howls." width 2
+    wedge "Leaves rustle."
+}
+
+trigger "hint" when always {
+    if ambient ambient in rooms porch {
+        do add wedge "Thunder." width 3 spinner ambient
+    }
+    do spinner message ambient
+}
+"#;
+
+        let tree = parse_source(source);
+        let root = tree.root_node();
+        let queries = Queries::new();
+
+        let mut cursor = QueryCursor::new();
+        let mut defs = Vec::new();
+        let mut matches = cursor.matches(&queries.spinner_definitions, root, source.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                defs.push(slice_text(source, &capture.node).trim().to_string());
+                let spinner_node = capture.node.parent().expect("spinner_def");
+                assert_eq!(extract_spinner_metadata(&spinner_node, source), (2, 3));
+            }
+        }
+        assert_eq!(defs, vec!["ambient".to_string()]);
+
+        let mut cursor = QueryCursor::new();
+        let mut refs = Vec::new();
+        let mut matches = cursor.matches(&queries.spinner_references, root, source.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                refs.push(slice_text(source, &capture.node).trim().to_string());
+            }
+        }
+        assert_eq!(refs.len(), 3);
+        assert!(refs.iter().all(|id| id == "ambient"));
+    }
+
+    #[test]
+    fn detects_goal_reference_context_in_goal_conditions() {
+        let source = "goal find-office {\n    name \"Find\"\n    done when reached room office\n}\n\ngoal check-in {\n    name \"Check In\"\n    start when goal complete find-office\n}\n";
+        let position = position_for_token(source, 7, "find-office", 2);
+        let symbol = completion_at(source, position);
+        assert_eq!(symbol, Some(SymbolKind::Goal));
+    }
+
+    #[test]
+    fn extracts_goal_metadata_and_formats_hover() {
+        let source = r#"goal check-in {
+    name "Check In"
+    desc "Report to the office."
+    group required
+    start when goal complete find-office
+    done when has flag checked-in
+}
+"#;
+
+        let tree = parse_source(source);
+        let root = tree.root_node();
+        let queries = Queries::new();
+
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&queries.goal_definitions, root, source.as_bytes());
+        let capture = matches.next().expect("goal definition").captures[0];
+        let goal_node = capture.node.parent().expect("goal_def");
+        let meta = extract_goal_metadata(&Document::new(source.to_string()), &goal_node, source);
+
+        assert_eq!(meta.name.as_deref(), Some("Check In"));
+        assert_eq!(meta.group.as_deref(), Some("required"));
+        assert_eq!(
+            meta.start_condition.as_deref(),
+            Some("goal complete find-office")
+        );
+        assert_eq!(meta.done_condition.as_deref(), Some("has flag checked-in"));
+        assert!(meta.fail_condition.is_none());
+        assert_eq!(
+            meta.start
+                .as_ref()
+                .map(|condition| (condition.kind, condition.id.as_str())),
+            Some((GoalConditionKind::GoalComplete, "find-office"))
+        );
+        assert_eq!(
+            meta.done
+                .as_ref()
+                .map(|condition| (condition.kind, condition.id.as_str())),
+            Some((GoalConditionKind::HasFlag, "checked-in"))
+        );
+
+        let hover = format_goal_hover("check-in", &meta, None);
+        assert!(hover.contains("**GOAL:** Check In (check-in)"));
+        assert!(hover.contains("- **Done when:** has flag checked-in"));
+        assert!(!hover.contains("Fails when"));
+
+        let mut cursor = QueryCursor::new();
+        let mut refs = Vec::new();
+        let mut matches = cursor.matches(&queries.goal_references, root, source.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                refs.push(slice_text(source, &capture.node).trim().to_string());
+            }
+        }
+        assert_eq!(refs, vec!["find-office".to_string()]);
+    }
+
+    #[test]
+    fn extracts_trigger_event_and_name_range() {
+        let source = "trigger \"Porch: Ring Bell\" only once\nwhen use item bell ability ring {\n    do show \"Ding.\"\n}\n";
+
+        let tree = parse_source(source);
+        let root = tree.root_node();
+        let queries = Queries::new();
+        let document = Document::new(source.to_string());
+
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&queries.trigger_definitions, root, source.as_bytes());
+        let capture = matches.next().expect("trigger definition").captures[0];
+        assert_eq!(
+            normalize_string_literal(slice_text(source, &capture.node)),
+            "Porch: Ring Bell"
+        );
+
+        let trigger_node = capture.node.parent().expect("trigger_def");
+        let meta = extract_trigger_metadata(&trigger_node, source);
+        assert_eq!(meta.event.as_deref(), Some("use item bell ability ring"));
+        assert!(meta.only_once);
+
+        let rename_range =
+            string_contents_range(&document, &capture.node, source).expect("quoted name");
+        assert_eq!(rename_range.start.character, 9);
+        assert_eq!(rename_range.end.character, 25);
+    }
+
+    #[test]
+    fn query_indexing_resolves_npc_states_per_npc() {
+        let source = r#"npc bob {
+    name "Bob"
+    state custom sulking
+    dialogue happy {
+        "Hi!"
+        "Hello!"
+    }
+    dialogue custom sulking {
+        "Hmph."
+    }
+}
+
+trigger "cheer up" when always {
+    if npc in state bob custom "sulking" {
+        do set npc state bob happy
+    }
+}
+
+room porch {
+    overlay if npc bob here {
+        happy "Bob waves."
+        custom(sulking) "Bob sulks."
+    }
+}
+
+trigger "sulk" when always {
+    do set npc state bob custom:sulking
+}
+"#;
+
+        let tree = parse_source(source);
+        let root = tree.root_node();
+        let queries = Queries::new();
+
+        let collect = |query: &tree_sitter::Query| {
+            let mut cursor = QueryCursor::new();
+            let mut found = Vec::new();
+            let mut matches = cursor.matches(query, root, source.as_bytes());
+            while let Some(m) = matches.next() {
+                for capture in m.captures {
+                    let token = npc_state_token(capture.node).expect("state token");
+                    let npc_id = enclosing_npc_id(capture.node, source).expect("npc id");
+                    let state = npc_state_name(slice_text(source, &token));
+                    if state != "custom" {
+                        found.push(npc_state_id(&npc_id, &state));
+                    }
+                }
+            }
+            found
+        };
+
+        assert_eq!(
+            collect(&queries.npc_state_definitions),
+            vec!["bob::happy".to_string(), "bob::sulking".to_string()]
+        );
+        assert_eq!(
+            collect(&queries.npc_state_references),
+            vec![
+                "bob::sulking".to_string(),
+                "bob::happy".to_string(),
+                "bob::happy".to_string(),
+                "bob::sulking".to_string(),
+                "bob::sulking".to_string(),
+            ]
+        );
+
+        let npc_node = root.named_child(0).expect("npc_def");
+        assert_eq!(
+            extract_npc_dialogue_states(&npc_node, source),
+            vec!["happy".to_string(), "sulking".to_string()]
+        );
+    }
+
+    #[test]
+    fn detects_npc_state_context_in_state_changes() {
+        let source = "trigger \"t\" when always {\n    do set npc state bob happy\n}\n";
+        let position = position_for_token(source, 1, "happy", 2);
+        assert_eq!(completion_at(source, position), Some(SymbolKind::NpcState));
+    }
+
+    #[test]
+    fn query_indexing_scopes_abilities_to_items() {
+        let source = r#"item lamp {
+    name "Lamp"
+    ability TurnOn
+    ability Unlock crate
+}
+
+trigger "light" when use item lamp ability turnOn {
+    do modify item lamp {
+        add ability Ignite
+    }
+}
+"#;
+
+        let tree = parse_source(source);
+        let root = tree.root_node();
+        let queries = Queries::new();
+
+        let mut cursor = QueryCursor::new();
+        let mut defs = Vec::new();
+        let mut matches =
+            cursor.matches(&queries.item_ability_definitions, root, source.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let item_id = enclosing_item_id(capture.node, source).expect("item id");
+                defs.push(item_ability_id(&item_id, slice_text(source, &capture.node)));
+            }
+        }
+        assert_eq!(
+            defs,
+            vec![
+                "lamp::turnon".to_string(),
+                "lamp::unlock".to_string(),
+                "lamp::ignite".to_string(),
+            ]
+        );
+
+        let mut cursor = QueryCursor::new();
+        let mut refs = Vec::new();
+        let mut matches = cursor.matches(&queries.item_ability_references, root, source.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let item_id = enclosing_item_id(capture.node, source).expect("item id");
+                refs.push(item_ability_id(&item_id, slice_text(source, &capture.node)));
+            }
+        }
+        assert_eq!(refs, vec!["lamp::turnon".to_string()]);
+    }
+
+    #[test]
+    fn detects_item_ability_context_in_use_events() {
+        let source = "trigger \"t\" when use item lamp ability turnOn {\n    do show \"\"\n}\n";
+        let position = position_for_token(source, 0, "turnOn", 2);
+        assert_eq!(
+            completion_at(source, position),
+            Some(SymbolKind::ItemAbility)
+        );
+    }
+
+    #[test]
+    fn reachability_follows_exits_reveals_patches_and_pushes() {
+        let source = r#"room start {
+    exit north -> hall
+}
+
+room hall {
+    exit south -> start
+}
+
+room vault {
+    exit up -> hall
+}
+
+room attic {
+}
+
+room cellar {
+}
+
+room garden {
+}
+
+room orphan {
+}
+
+trigger "t" when always {
+    do reveal exit from hall to attic direction up
+    do modify room attic {
+        add exit down -> cellar
+    }
+    do push player to garden
+}
+"#;
+
+        let tree = parse_source(source);
+        let edges = collect_room_edges(tree.root_node(), source);
+        let reachable = reachable_rooms(&["start".to_string()], &edges);
+
+        for room in ["start", "hall", "attic", "cellar", "garden"] {
+            assert!(reachable.contains(room), "{} should be reachable", room);
+        }
+        assert!(!reachable.contains("vault"));
+        assert!(!reachable.contains("orphan"));
+    }
+
+    #[test]
+    fn disk_copies_never_replace_open_documents() {
+        let (service, _socket) = tower_lsp::LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = Url::parse("file:///world/rooms.amble").unwrap();
+        backend.open_documents.insert(uri.to_string());
+        backend.analyze_document(&uri, "room edited {\n}\n");
+
+        let mut parser = amble_parser();
+        backend.analyze_disk_copy(&mut parser, &uri, "room on-disk {\n}\n", None);
+        assert!(backend.symbols.rooms.has_definition("edited"));
+        assert!(!backend.symbols.rooms.has_definition("on-disk"));
+
+        backend.open_documents.remove(&uri.to_string());
+        backend.analyze_disk_copy(&mut parser, &uri, "room on-disk {\n}\n", None);
+        assert!(backend.symbols.rooms.has_definition("on-disk"));
+    }
+
+    #[test]
+    fn duplicate_trigger_names_warn_at_each_definition() {
+        let (service, _socket) = tower_lsp::LspService::new(Backend::new);
+        let backend = service.inner();
+        let first = Url::parse("file:///world/a.amble").unwrap();
+        let second = Url::parse("file:///world/b.amble").unwrap();
+        let trigger = "trigger \"Ring bell\" when always {\n    do show \"Ding.\"\n}\n";
+        backend.analyze_document(&first, trigger);
+        backend.analyze_document(&second, trigger);
+
+        let diagnostics = backend
+            .collect_diagnostics(&first, &backend.workspace_graphs())
+            .unwrap();
+        let duplicates: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.code == rule_code("duplicate-trigger-name"))
+            .collect();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            duplicates[0].message,
+            "Trigger name 'Ring bell' is used by 2 triggers; names must be unique"
+        );
+        let related = duplicates[0].related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.uri, second);
+    }
+
+    #[test]
+    fn builtin_spinners_are_never_unused() {
+        let (service, _socket) = tower_lsp::LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = Url::parse("file:///world/spinners.amble").unwrap();
+        backend.analyze_document(
+            &uri,
+            "spinner quitMsg {\n  wedge \"Bye.\" width 1\n}\n\n\
+             spinner ambientHum {\n  wedge \"Hmm.\" width 1\n}\n",
+        );
+
+        let diagnostics = backend
+            .collect_diagnostics(&uri, &backend.workspace_graphs())
+            .unwrap();
+        let unused: Vec<&str> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.code == rule_code("unused-definition"))
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(unused, vec!["Spinner 'ambientHum' is never referenced"]);
+    }
 }
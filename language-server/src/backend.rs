@@ -1,11 +1,19 @@
-use crate::analysis::{format_hover, PlayerStart};
+use crate::analysis::{format_hover, FlagUse, NpcStateChange, PlayerStart, RoomEdge};
 use crate::formatter;
+use crate::graphs::GraphKind;
 use crate::queries::Queries;
-use crate::symbols::{SymbolDefinition, SymbolIndex, SymbolKind, SymbolMetadata, SymbolStore};
-use crate::text::DocumentStore;
+use crate::semantic_tokens::semantic_tokens_legend;
+use crate::settings::Settings;
+use crate::symbols::{
+    split_scoped_id, SymbolDefinition, SymbolIndex, SymbolKind, SymbolMetadata, SymbolStore,
+};
+use crate::syntax::SyntaxError;
+use crate::text::{Document, DocumentStore};
 use dashmap::{DashMap, DashSet};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 use std::sync::Arc;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
@@ -13,7 +21,13 @@ use tower_lsp::{Client, LanguageServer};
 use tree_sitter::Parser;
 
 const COMPLETION_DETAIL_MAX_CHARS: usize = 80;
+const AMBLE_FILE_GLOB: &str = "**/*.amble";
+/// Globs can't match directories alone, so folders being created or deleted are watched
+/// through creates and deletes of any path; `apply_file_event` drops those under
+/// `IGNORED_DIRECTORIES` such as `target` and `.git`.
+const ANY_PATH_GLOB: &str = "**/*";
 
+#[derive(Clone)]
 pub struct Backend {
     pub(crate) client: Client,
     pub(crate) symbols: Arc<SymbolStore>,
@@ -24,18 +38,47 @@ pub struct Backend {
     pub(crate) queries: Arc<Queries>,
     /// Files currently open in the editor. We never overwrite these from on-disk scans.
     pub(crate) open_documents: Arc<DashSet<String>>,
+    /// Read while a file from disk is checked against `open_documents` and analyzed, written
+    /// while `did_open` adds to `open_documents`, so an open buffer is never replaced by the
+    /// copy on disk.
+    pub(crate) open_guard: Arc<parking_lot::RwLock<()>>,
     /// Last observed on-disk modified time per indexed file URI.
     pub(crate) indexed_documents: Arc<DashMap<String, Option<std::time::SystemTime>>>,
     /// Cached `player_start` nodes per document; used for workspace-level diagnostics.
     pub(crate) player_starts: Arc<DashMap<String, Vec<PlayerStart>>>,
+    /// Room-to-room movement edges per document; used for reachability diagnostics.
+    pub(crate) room_edges: Arc<DashMap<String, Vec<RoomEdge>>>,
+    /// Where each document sets, reads and removes flags; used for the flag graph export.
+    pub(crate) flag_uses: Arc<DashMap<String, Vec<FlagUse>>>,
+    /// Parse errors from the most recent analysis of each document.
+    pub(crate) syntax_errors: Arc<DashMap<String, Vec<SyntaxError>>>,
+    /// NPC state changes made by triggers in each document.
+    pub(crate) npc_state_changes: Arc<DashMap<String, Vec<NpcStateChange>>>,
+    /// Latest settings from the client.
+    pub(crate) settings: Arc<parking_lot::RwLock<Settings>>,
+    /// What the client announced it supports in `initialize`.
+    pub(crate) client_capabilities: Arc<parking_lot::RwLock<ClientCapabilities>>,
+    /// Documents whose diagnostics are stale and will be re-published on the next flush.
+    pub(crate) pending_diagnostics: Arc<DashSet<String>>,
+    /// Bumped on every scheduled flush so only the last one in a burst of edits publishes.
+    pub(crate) diagnostics_generation: Arc<AtomicU64>,
+    /// Set when definitions, display names or sequence limits change; the next flush asks the
+    /// client to refresh semantic tokens and inlay hints.
+    pub(crate) presentation_stale: Arc<AtomicBool>,
+}
+
+/// A parser for the Amble grammar.
+pub(crate) fn amble_parser() -> Parser {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_amble::language())
+        .expect("Error loading Amble grammar");
+    parser
 }
 
 impl Backend {
     pub fn new(client: Client) -> Self {
-        let mut parser = Parser::new();
-        parser
-            .set_language(&tree_sitter_amble::language())
-            .expect("Error loading Amble grammar");
+        let parser = amble_parser();
 
         Self {
             client,
@@ -46,8 +89,18 @@ impl Backend {
             parser: Arc::new(parking_lot::Mutex::new(parser)),
             queries: Arc::new(Queries::new()),
             open_documents: Arc::new(DashSet::new()),
+            open_guard: Arc::new(parking_lot::RwLock::new(())),
             indexed_documents: Arc::new(DashMap::new()),
             player_starts: Arc::new(DashMap::new()),
+            room_edges: Arc::new(DashMap::new()),
+            flag_uses: Arc::new(DashMap::new()),
+            syntax_errors: Arc::new(DashMap::new()),
+            npc_state_changes: Arc::new(DashMap::new()),
+            settings: Arc::new(parking_lot::RwLock::new(Settings::default())),
+            client_capabilities: Arc::new(parking_lot::RwLock::new(ClientCapabilities::default())),
+            pending_diagnostics: Arc::new(DashSet::new()),
+            diagnostics_generation: Arc::new(AtomicU64::new(0)),
+            presentation_stale: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -116,6 +169,24 @@ impl Backend {
             &self.symbols.action_sets,
             &mut symbols,
         );
+        self.push_document_symbols_for_index(
+            uri,
+            SymbolKind::Spinner,
+            &self.symbols.spinners,
+            &mut symbols,
+        );
+        self.push_document_symbols_for_index(
+            uri,
+            SymbolKind::Goal,
+            &self.symbols.goals,
+            &mut symbols,
+        );
+        self.push_document_symbols_for_index(
+            uri,
+            SymbolKind::Trigger,
+            &self.symbols.triggers,
+            &mut symbols,
+        );
         symbols
     }
 
@@ -179,6 +250,24 @@ impl Backend {
             &self.symbols.action_sets,
             &mut symbols,
         );
+        self.push_workspace_symbols_for_index(
+            query,
+            SymbolKind::Spinner,
+            &self.symbols.spinners,
+            &mut symbols,
+        );
+        self.push_workspace_symbols_for_index(
+            query,
+            SymbolKind::Goal,
+            &self.symbols.goals,
+            &mut symbols,
+        );
+        self.push_workspace_symbols_for_index(
+            query,
+            SymbolKind::Trigger,
+            &self.symbols.triggers,
+            &mut symbols,
+        );
         symbols
     }
 
@@ -255,7 +344,50 @@ impl Backend {
         fallback
     }
 
-    fn definition_display_path(&self, uri: &Url) -> Option<String> {
+    /// Asks the client to report `.amble` files created, changed or deleted outside the editor,
+    /// as well as folders being created or deleted. Only clients that support registering the
+    /// watchers dynamically are asked.
+    async fn register_file_watcher(&self) {
+        let dynamic_registration = self
+            .client_capabilities
+            .read()
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        if !dynamic_registration {
+            return;
+        }
+
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(AMBLE_FILE_GLOB.to_string()),
+                    kind: None,
+                },
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(ANY_PATH_GLOB.to_string()),
+                    kind: Some(WatchKind::Create | WatchKind::Delete),
+                },
+            ],
+        };
+        let registration = Registration {
+            id: "amble-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        if let Err(error) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Could not watch .amble files: {}", error),
+                )
+                .await;
+        }
+    }
+
+    pub(crate) fn definition_display_path(&self, uri: &Url) -> Option<String> {
         let file_path = uri.to_file_path().ok()?;
         let mut best_match: Option<(usize, PathBuf)> = None;
         {
@@ -354,6 +486,18 @@ fn definition_detail(definition: &SymbolDefinition) -> Option<String> {
         }
         SymbolMetadata::Cond(meta) => Some(meta.expression.clone()),
         SymbolMetadata::ActionSet(meta) => Some(meta.body.clone()),
+        SymbolMetadata::Spinner(meta) => Some(format!(
+            "{} wedges, total width {}",
+            meta.wedge_count, meta.total_width
+        )),
+        SymbolMetadata::Goal(meta) => meta
+            .name
+            .clone()
+            .or_else(|| meta.group.clone())
+            .or_else(|| meta.description.clone()),
+        SymbolMetadata::Trigger(meta) => meta.event.clone(),
+        SymbolMetadata::NpcState(meta) => Some(format!("{} dialogue lines", meta.line_count)),
+        SymbolMetadata::ItemAbility(meta) => Some(format!("Ability of {}", meta.item_id)),
     }
 }
 
@@ -366,6 +510,11 @@ fn lsp_symbol_kind(kind: SymbolKind) -> tower_lsp::lsp_types::SymbolKind {
         SymbolKind::Set => tower_lsp::lsp_types::SymbolKind::NAMESPACE,
         SymbolKind::Cond => tower_lsp::lsp_types::SymbolKind::CONSTANT,
         SymbolKind::ActionSet => tower_lsp::lsp_types::SymbolKind::FUNCTION,
+        SymbolKind::Spinner => tower_lsp::lsp_types::SymbolKind::ENUM,
+        SymbolKind::Goal => tower_lsp::lsp_types::SymbolKind::MODULE,
+        SymbolKind::Trigger => tower_lsp::lsp_types::SymbolKind::EVENT,
+        SymbolKind::NpcState => tower_lsp::lsp_types::SymbolKind::ENUM_MEMBER,
+        SymbolKind::ItemAbility => tower_lsp::lsp_types::SymbolKind::METHOD,
     }
 }
 
@@ -378,6 +527,11 @@ fn completion_item_kind(kind: SymbolKind) -> CompletionItemKind {
         SymbolKind::Set => CompletionItemKind::MODULE,
         SymbolKind::Cond => CompletionItemKind::CONSTANT,
         SymbolKind::ActionSet => CompletionItemKind::FUNCTION,
+        SymbolKind::Spinner => CompletionItemKind::ENUM,
+        SymbolKind::Goal => CompletionItemKind::EVENT,
+        SymbolKind::Trigger => CompletionItemKind::EVENT,
+        SymbolKind::NpcState => CompletionItemKind::ENUM_MEMBER,
+        SymbolKind::ItemAbility => CompletionItemKind::METHOD,
     }
 }
 
@@ -407,6 +561,14 @@ fn query_matches_symbol(name: &str, detail: Option<&str>, query: &str) -> bool {
 impl LanguageServer for Backend {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
         self.update_workspace_roots(&params);
+        *self.client_capabilities.write() = params.capabilities.clone();
+        if let Some(settings) = params
+            .initialization_options
+            .as_ref()
+            .and_then(Settings::from_value)
+        {
+            *self.settings.write() = settings;
+        }
 
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
@@ -415,7 +577,7 @@ impl LanguageServer for Backend {
             }),
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
@@ -423,7 +585,43 @@ impl LanguageServer for Backend {
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 completion_provider: Some(CompletionOptions::default()),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        ..CodeActionOptions::default()
+                    },
+                )),
                 document_formatting_provider: Some(OneOf::Left(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![" ".to_string(), "\"".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens_legend(),
+                            range: Some(true),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            ..SemanticTokensOptions::default()
+                        },
+                    ),
+                ),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: GraphKind::ALL
+                        .iter()
+                        .map(|kind| kind.command().to_string())
+                        .collect(),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
                 rename_provider: Some(OneOf::Right(RenameOptions {
                     prepare_provider: Some(true),
                     work_done_progress_options: WorkDoneProgressOptions::default(),
@@ -434,9 +632,11 @@ impl LanguageServer for Backend {
     }
 
     async fn initialized(&self, _: InitializedParams) {
+        self.register_file_watcher().await;
         self.client
             .log_message(MessageType::INFO, "Amble LSP server initialized")
             .await;
+        self.index_workspace().await;
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -448,7 +648,10 @@ impl LanguageServer for Backend {
         let uri_str = uri.to_string();
         let text = params.text_document.text;
 
-        self.open_documents.insert(uri_str.clone());
+        {
+            let _guard = self.open_guard.write();
+            self.open_documents.insert(uri_str.clone());
+        }
         if let Ok(path) = uri.to_file_path() {
             self.indexed_documents
                 .insert(uri_str.clone(), file_modified(&path));
@@ -456,7 +659,7 @@ impl LanguageServer for Backend {
 
         self.analyze_document(&uri, &text);
         self.scan_directory(&uri).await;
-        self.check_workspace_diagnostics().await;
+        self.publish_pending_diagnostics().await;
 
         self.client
             .log_message(MessageType::INFO, format!("Opened document: {}", uri))
@@ -467,11 +670,22 @@ impl LanguageServer for Backend {
         let uri = params.text_document.uri;
         let uri_str = uri.to_string();
 
-        if let Some(change) = params.content_changes.into_iter().next() {
-            self.open_documents.insert(uri_str);
-            self.analyze_document(&uri, &change.text);
-            self.check_workspace_diagnostics().await;
+        if params.content_changes.is_empty() {
+            return;
+        }
+
+        let mut document = self
+            .documents
+            .get(&uri_str)
+            .map(|doc| doc.clone())
+            .unwrap_or_else(|| Document::new(String::new()));
+        for change in params.content_changes {
+            document.apply_change(change);
         }
+
+        self.open_documents.insert(uri_str);
+        self.analyze_parsed_document(&uri, document);
+        self.schedule_diagnostics();
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
@@ -483,7 +697,7 @@ impl LanguageServer for Backend {
         }
 
         self.scan_directory(&uri).await;
-        self.check_workspace_diagnostics().await;
+        self.publish_pending_diagnostics().await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -497,21 +711,51 @@ impl LanguageServer for Backend {
                 self.indexed_documents
                     .insert(uri_str.clone(), file_modified(&path));
             } else {
-                self.symbols.clear_document(&uri);
-                self.documents.remove(&uri_str);
-                self.document_symbols.remove(&uri_str);
-                self.player_starts.remove(&uri_str);
-                self.indexed_documents.remove(&uri_str);
+                self.forget_document(&uri);
             }
         } else {
-            self.symbols.clear_document(&uri);
-            self.documents.remove(&uri_str);
-            self.document_symbols.remove(&uri_str);
-            self.player_starts.remove(&uri_str);
-            self.indexed_documents.remove(&uri_str);
+            self.forget_document(&uri);
+        }
+
+        self.publish_pending_diagnostics().await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        // `.amble` files match both watchers, so the same event can be reported twice.
+        let mut seen = HashSet::new();
+        for event in &params.changes {
+            if seen.insert(event) {
+                self.apply_file_event(event);
+            }
+        }
+        self.publish_pending_diagnostics().await;
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        self.apply_workspace_folder_change(&params.event);
+        self.publish_pending_diagnostics().await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        let Some(settings) = Settings::from_value(&params.settings) else {
+            return;
+        };
+        *self.settings.write() = settings;
+        let (_, inlay_hints) = self.refresh_support();
+        if inlay_hints {
+            let _ = self.client.inlay_hint_refresh().await;
         }
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        Ok(Some(
+            self.inlay_hints(&params.text_document.uri, params.range),
+        ))
+    }
 
-        self.check_workspace_diagnostics().await;
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let position = params.text_document_position_params;
+        Ok(self.signature_help(&position.text_document.uri, position.position))
     }
 
     async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
@@ -582,7 +826,42 @@ impl LanguageServer for Backend {
             }
         }
 
-        Ok(None)
+        Ok(self.keyword_hover(&uri, position))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let data = self.semantic_tokens(&params.text_document.uri, None);
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let data = self.semantic_tokens(&params.text_document.uri, Some(params.range));
+        Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let actions =
+            self.undefined_symbol_actions(&params.text_document.uri, &params.context.diagnostics);
+        if actions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(actions))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        self.execute_graph_command(params)
     }
 
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
@@ -623,9 +902,15 @@ impl LanguageServer for Backend {
                 &occurrence.id,
                 occurrence.range,
             );
+            let placeholder = match occurrence.kind {
+                SymbolKind::NpcState | SymbolKind::ItemAbility => split_scoped_id(&occurrence.id)
+                    .map(|(_, name)| name.to_string())
+                    .unwrap_or(occurrence.id),
+                _ => occurrence.id,
+            };
             return Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
                 range,
-                placeholder: occurrence.id,
+                placeholder,
             }));
         }
 
@@ -692,10 +977,35 @@ impl LanguageServer for Backend {
             let index = self.symbols.index(symbol_type);
             let mut items = Vec::new();
 
-            for entry in index.definitions_iter() {
-                let id = entry.key().clone();
-                let definition = entry.value().clone();
-                items.push(self.completion_item_from_definition(symbol_type, &id, &definition));
+            if matches!(symbol_type, SymbolKind::NpcState | SymbolKind::ItemAbility) {
+                // States and abilities are scoped; only offer those of the NPC or item in scope.
+                let Some(owner_id) = self.get_completion_owner(&uri, position, symbol_type) else {
+                    return Ok(None);
+                };
+                for entry in index.definitions_iter() {
+                    let Some((owner, name)) = split_scoped_id(entry.key()) else {
+                        continue;
+                    };
+                    if owner != owner_id {
+                        continue;
+                    }
+                    let definition = entry.value().clone();
+                    let label = match &definition.metadata {
+                        SymbolMetadata::ItemAbility(meta) => meta.ability.clone(),
+                        _ => name.to_string(),
+                    };
+                    items.push(self.completion_item_from_definition(
+                        symbol_type,
+                        &label,
+                        &definition,
+                    ));
+                }
+            } else {
+                for entry in index.definitions_iter() {
+                    let id = entry.key().clone();
+                    let definition = entry.value().clone();
+                    items.push(self.completion_item_from_definition(symbol_type, &id, &definition));
+                }
             }
 
             if !items.is_empty() {
@@ -703,6 +1013,11 @@ impl LanguageServer for Backend {
             }
         }
 
+        let items = self.keyword_completions(&uri, position);
+        if !items.is_empty() {
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+
         Ok(None)
     }
 }
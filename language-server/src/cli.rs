@@ -0,0 +1,410 @@
+use crate::analysis::amble_files_under;
+use crate::backend::{amble_parser, Backend};
+use crate::formatter;
+use crate::graphs::{GraphFormat, GraphKind};
+use crate::report::{json_report, sarif_report, text_line, Finding, WorldPaths};
+use std::path::Path;
+use std::process::ExitCode;
+use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
+use tower_lsp::LspService;
+
+const USAGE: &str = "\
+usage: amble-lsp                    run the language server over stdio
+       amble-lsp check [--format text|json|sarif] <world-dir>
+                                    lint every .amble file under <world-dir>
+       amble-lsp fmt [--check] <path>...
+                                    format .amble files (directories are searched)
+       amble-lsp graph rooms|flags|goals [--format dot|mermaid|json] [--output <file>] <world-dir>
+                                    export the room exit, flag or goal dependency graph";
+
+/// What `amble-lsp` was asked to do on the command line.
+pub(crate) enum Invocation {
+    Serve,
+    Exit(ExitCode),
+}
+
+/// Parsed `graph` arguments.
+#[derive(Debug, PartialEq, Eq)]
+struct GraphArgs {
+    kind: GraphKind,
+    format: GraphFormat,
+    output: Option<String>,
+    world_dir: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+pub(crate) fn run(args: &[String]) -> Invocation {
+    let Some((command, rest)) = args.split_first() else {
+        return Invocation::Serve;
+    };
+
+    let code = match command.as_str() {
+        "--stdio" => return Invocation::Serve,
+        "check" => check(rest),
+        "fmt" => fmt(rest),
+        "graph" => graph(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        other => usage_error(&format!("unknown command '{}'", other)),
+    };
+    Invocation::Exit(code)
+}
+
+/// Indexes `<world-dir>` exactly as the server would and reports its diagnostics, as
+/// `file:line:col: severity: message` lines by default. Fails if any diagnostic is an error.
+fn check(args: &[String]) -> ExitCode {
+    let (format, world_dir) = match parse_check_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return usage_error(&message),
+    };
+    let root = match std::fs::canonicalize(&world_dir) {
+        Ok(root) if root.is_dir() => root,
+        _ => return usage_error(&format!("'{}' is not a directory", world_dir)),
+    };
+
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner();
+    let files = index_world(backend, &root);
+    let paths = WorldPaths { world_dir, root };
+
+    let graphs = backend.workspace_graphs();
+    let mut findings = Vec::new();
+    for uri in &files {
+        let mut diagnostics = backend
+            .collect_diagnostics(uri, &graphs)
+            .unwrap_or_default();
+        diagnostics.sort_by_key(|diagnostic| {
+            (
+                diagnostic.range.start.line,
+                diagnostic.range.start.character,
+            )
+        });
+        for diagnostic in diagnostics {
+            let actions = backend.undefined_symbol_actions(uri, std::slice::from_ref(&diagnostic));
+            findings.push(Finding::new(uri.clone(), diagnostic, actions));
+        }
+    }
+
+    match format {
+        ReportFormat::Text => {
+            for finding in &findings {
+                println!("{}", text_line(&paths, finding));
+            }
+        }
+        ReportFormat::Json => println!("{:#}", json_report(&paths, &findings)),
+        ReportFormat::Sarif => println!("{:#}", sarif_report(&paths, &findings)),
+    }
+
+    let count = |severity| {
+        findings
+            .iter()
+            .filter(|finding| finding.diagnostic.severity == Some(severity))
+            .count()
+    };
+    let errors = count(DiagnosticSeverity::ERROR);
+    eprintln!(
+        "{} error(s), {} warning(s) in {} file(s)",
+        errors,
+        count(DiagnosticSeverity::WARNING),
+        files.len()
+    );
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn parse_check_args(args: &[String]) -> Result<(ReportFormat, String), String> {
+    let mut format = ReportFormat::Text;
+    let mut world_dir = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--format" => args
+                .next()
+                .ok_or_else(|| "--format needs a value".to_string())?
+                .as_str(),
+            other => match other.strip_prefix("--format=") {
+                Some(value) => value,
+                None if other.starts_with('-') => {
+                    return Err(format!("unknown option '{}'", other));
+                }
+                None if world_dir.is_some() => {
+                    return Err("check takes exactly one <world-dir>".to_string());
+                }
+                None => {
+                    world_dir = Some(other.to_string());
+                    continue;
+                }
+            },
+        };
+        format = match value {
+            "text" => ReportFormat::Text,
+            "json" => ReportFormat::Json,
+            "sarif" => ReportFormat::Sarif,
+            other => return Err(format!("unknown format '{}'", other)),
+        };
+    }
+
+    let world_dir = world_dir.ok_or_else(|| "check needs a <world-dir>".to_string())?;
+    Ok((format, world_dir))
+}
+
+/// Rewrites files in the canonical layout, or with `--check` only lists the files that
+/// would change and fails if there are any.
+fn fmt(args: &[String]) -> ExitCode {
+    let (check_only, targets) = match parse_fmt_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return usage_error(&message),
+    };
+
+    let mut files = Vec::new();
+    for target in &targets {
+        let path = Path::new(target);
+        if path.is_dir() {
+            let mut found = amble_files_under(path);
+            found.sort();
+            files.extend(found);
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        } else {
+            return usage_error(&format!("'{}' does not exist", target));
+        }
+    }
+
+    let mut changed = 0;
+    let mut failed = false;
+    for path in &files {
+        match format_file(path, check_only) {
+            Ok(false) => {}
+            Ok(true) => {
+                changed += 1;
+                if check_only {
+                    println!("would reformat {}", path.display());
+                } else {
+                    println!("formatted {}", path.display());
+                }
+            }
+            Err(error) => {
+                eprintln!("amble-lsp: {}: {}", path.display(), error);
+                failed = true;
+            }
+        }
+    }
+
+    if failed || (check_only && changed > 0) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn parse_fmt_args(args: &[String]) -> Result<(bool, Vec<String>), String> {
+    let mut check_only = false;
+    let mut targets = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check_only = true,
+            other if other.starts_with('-') => {
+                return Err(format!("unknown option '{}'", other));
+            }
+            other => targets.push(other.to_string()),
+        }
+    }
+    if targets.is_empty() {
+        return Err("fmt needs at least one <path>".to_string());
+    }
+    Ok((check_only, targets))
+}
+
+/// Formats one file; returns whether its contents differ from the canonical layout.
+fn format_file(path: &Path, check_only: bool) -> std::io::Result<bool> {
+    let current = std::fs::read_to_string(path)?;
+    let formatted = formatter::format_document(&current);
+    if formatted == current {
+        return Ok(false);
+    }
+    if !check_only {
+        std::fs::write(path, formatted)?;
+    }
+    Ok(true)
+}
+
+/// Indexes `<world-dir>` and prints the requested graph, or writes it to `--output`.
+fn graph(args: &[String]) -> ExitCode {
+    let args = match parse_graph_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return usage_error(&message),
+    };
+    let root = match std::fs::canonicalize(&args.world_dir) {
+        Ok(root) if root.is_dir() => root,
+        _ => return usage_error(&format!("'{}' is not a directory", args.world_dir)),
+    };
+
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner();
+    index_world(backend, &root);
+    let graph = backend.export_graph(args.kind, args.format);
+
+    match args.output {
+        Some(output) => {
+            if let Err(error) = std::fs::write(&output, graph) {
+                eprintln!("amble-lsp: {}: {}", output, error);
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{}", graph),
+    }
+    ExitCode::SUCCESS
+}
+
+fn parse_graph_args(args: &[String]) -> Result<GraphArgs, String> {
+    let (kind, rest) = args
+        .split_first()
+        .ok_or_else(|| "graph needs a graph name (rooms, flags or goals)".to_string())?;
+    let kind = GraphKind::parse(kind).ok_or_else(|| format!("unknown graph '{}'", kind))?;
+
+    let mut format = GraphFormat::Dot;
+    let mut output = None;
+    let mut world_dir = None;
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .or_else(|| rest.next().map(String::as_str))
+                .ok_or_else(|| format!("{} needs a value", option))
+        };
+        match option {
+            "--format" => {
+                let value = value()?;
+                format = GraphFormat::parse(value)
+                    .ok_or_else(|| format!("unknown format '{}'", value))?;
+            }
+            "--output" | "-o" => output = Some(value()?.to_string()),
+            other if other.starts_with('-') => {
+                return Err(format!("unknown option '{}'", other));
+            }
+            _ if world_dir.is_some() => {
+                return Err("graph takes exactly one <world-dir>".to_string());
+            }
+            other => world_dir = Some(other.to_string()),
+        }
+    }
+
+    let world_dir = world_dir.ok_or_else(|| "graph needs a <world-dir>".to_string())?;
+    Ok(GraphArgs {
+        kind,
+        format,
+        output,
+        world_dir,
+    })
+}
+
+/// Registers `root` as the only workspace root and indexes every `.amble` file under it,
+/// sorted by path so output is stable.
+fn index_world(backend: &Backend, root: &Path) -> Vec<Url> {
+    backend.workspace_roots.write().push(root.to_path_buf());
+
+    let mut parser = amble_parser();
+    let mut paths = amble_files_under(root);
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            backend.index_file(&mut parser, &path);
+            Url::from_file_path(&path).ok()
+        })
+        .collect()
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("amble-lsp: {}\n{}", message, USAGE);
+    ExitCode::from(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_check_format_and_world_dir() {
+        assert_eq!(
+            parse_check_args(&args(&["world"])),
+            Ok((ReportFormat::Text, "world".to_string()))
+        );
+        assert_eq!(
+            parse_check_args(&args(&["--format", "sarif", "world"])),
+            Ok((ReportFormat::Sarif, "world".to_string()))
+        );
+        assert_eq!(
+            parse_check_args(&args(&["world", "--format=json"])),
+            Ok((ReportFormat::Json, "world".to_string()))
+        );
+        assert!(parse_check_args(&args(&["--format", "xml", "world"])).is_err());
+        assert!(parse_check_args(&args(&["a", "b"])).is_err());
+        assert!(parse_check_args(&args(&[])).is_err());
+    }
+
+    #[test]
+    fn parses_fmt_check_flag_and_paths() {
+        assert_eq!(
+            parse_fmt_args(&args(&["--check", "rooms.amble", "world"])),
+            Ok((true, args(&["rooms.amble", "world"])))
+        );
+        assert_eq!(
+            parse_fmt_args(&args(&["world"])),
+            Ok((false, args(&["world"])))
+        );
+        assert!(parse_fmt_args(&args(&["--check"])).is_err());
+        assert!(parse_fmt_args(&args(&["--diff", "world"])).is_err());
+    }
+
+    #[test]
+    fn parses_graph_kind_format_and_output() {
+        assert_eq!(
+            parse_graph_args(&args(&[
+                "rooms",
+                "--format=mermaid",
+                "-o",
+                "map.mmd",
+                "world"
+            ])),
+            Ok(GraphArgs {
+                kind: GraphKind::Rooms,
+                format: GraphFormat::Mermaid,
+                output: Some("map.mmd".to_string()),
+                world_dir: "world".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_graph_args(&args(&["rooms", "world"])).map(|parsed| parsed.format),
+            Ok(GraphFormat::Dot)
+        );
+        assert_eq!(
+            parse_graph_args(&args(&["flags", "--format", "json", "world"]))
+                .map(|parsed| (parsed.kind, parsed.format)),
+            Ok((GraphKind::Flags, GraphFormat::Json))
+        );
+        assert!(parse_graph_args(&args(&["exits", "world"])).is_err());
+        assert!(parse_graph_args(&args(&["rooms", "--format", "svg", "world"])).is_err());
+        assert!(parse_graph_args(&args(&["rooms", "--output"])).is_err());
+        assert!(parse_graph_args(&args(&["rooms"])).is_err());
+    }
+}
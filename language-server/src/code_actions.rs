@@ -0,0 +1,337 @@
+use crate::backend::Backend;
+use crate::symbols::SymbolKind;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, Range, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+/// Payload attached to "Undefined ..." diagnostics so code actions know what to create or
+/// which existing ids to offer instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct UndefinedSymbol {
+    pub kind: String,
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+    /// The span holding the id itself, e.g. without a flag's `#step` suffix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replace_range: Option<Range>,
+}
+
+/// Builds the `data` field for an undefined-symbol diagnostic.
+pub(crate) fn undefined_symbol_data(
+    kind: SymbolKind,
+    id: &str,
+    suggestions: &[String],
+    replace_range: Range,
+) -> Option<serde_json::Value> {
+    if !has_stub(kind) && suggestions.is_empty() {
+        return None;
+    }
+    serde_json::to_value(UndefinedSymbol {
+        kind: kind_name(kind).to_string(),
+        id: id.to_string(),
+        suggestions: suggestions.to_vec(),
+        replace_range: Some(replace_range),
+    })
+    .ok()
+}
+
+fn has_stub(kind: SymbolKind) -> bool {
+    matches!(
+        kind,
+        SymbolKind::Room | SymbolKind::Item | SymbolKind::Npc | SymbolKind::Flag
+    )
+}
+
+fn kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::Room => "room",
+        SymbolKind::Item => "item",
+        SymbolKind::Npc => "npc",
+        SymbolKind::Flag => "flag",
+        SymbolKind::Set => "set",
+        SymbolKind::Cond => "cond",
+        SymbolKind::ActionSet => "action_set",
+        SymbolKind::Spinner => "spinner",
+        SymbolKind::Goal => "goal",
+        SymbolKind::Trigger => "trigger",
+        SymbolKind::NpcState => "npc_state",
+        SymbolKind::ItemAbility => "item_ability",
+    }
+}
+
+fn kind_from_name(name: &str) -> Option<SymbolKind> {
+    match name {
+        "room" => Some(SymbolKind::Room),
+        "item" => Some(SymbolKind::Item),
+        "npc" => Some(SymbolKind::Npc),
+        "flag" => Some(SymbolKind::Flag),
+        "set" => Some(SymbolKind::Set),
+        "cond" => Some(SymbolKind::Cond),
+        "action_set" => Some(SymbolKind::ActionSet),
+        "spinner" => Some(SymbolKind::Spinner),
+        "goal" => Some(SymbolKind::Goal),
+        "trigger" => Some(SymbolKind::Trigger),
+        "npc_state" => Some(SymbolKind::NpcState),
+        "item_ability" => Some(SymbolKind::ItemAbility),
+        _ => None,
+    }
+}
+
+impl Backend {
+    /// Offers "Replace with `x`" and "Create room `x` in rooms.amble" style fixes for
+    /// undefined-symbol diagnostics.
+    pub(crate) fn undefined_symbol_actions(
+        &self,
+        uri: &Url,
+        diagnostics: &[Diagnostic],
+    ) -> Vec<CodeActionOrCommand> {
+        let mut actions = Vec::new();
+        for diagnostic in diagnostics {
+            let Some(undefined) = diagnostic
+                .data
+                .clone()
+                .and_then(|data| serde_json::from_value::<UndefinedSymbol>(data).ok())
+            else {
+                continue;
+            };
+            let Some(kind) = kind_from_name(&undefined.kind) else {
+                continue;
+            };
+            let range = undefined.replace_range.unwrap_or(diagnostic.range);
+            for (index, suggestion) in undefined.suggestions.iter().enumerate() {
+                actions.push(CodeActionOrCommand::CodeAction(replace_with_action(
+                    uri,
+                    range,
+                    suggestion,
+                    index == 0,
+                    diagnostic,
+                )));
+            }
+            if let Some(mut action) =
+                self.create_definition_action(uri, kind, &undefined.id, diagnostic)
+            {
+                action.is_preferred = Some(undefined.suggestions.is_empty());
+                actions.push(CodeActionOrCommand::CodeAction(action));
+            }
+        }
+        actions
+    }
+
+    fn create_definition_action(
+        &self,
+        uri: &Url,
+        kind: SymbolKind,
+        id: &str,
+        diagnostic: &Diagnostic,
+    ) -> Option<CodeAction> {
+        if !has_stub(kind) || self.symbols.index(kind).has_definition(id) {
+            return None;
+        }
+
+        let target = self
+            .stub_target_document(kind)
+            .unwrap_or_else(|| uri.clone());
+        let document = self.documents.get(&target.to_string())?;
+        let text = document.text();
+        let end = document.position_at(text.len());
+        let edit = TextEdit {
+            range: Range { start: end, end },
+            new_text: format!("{}{}", stub_separator(text), definition_stub(kind, id)),
+        };
+
+        let file_label = self
+            .definition_display_path(&target)
+            .unwrap_or_else(|| target.to_string());
+        let mut changes = HashMap::new();
+        changes.insert(target, vec![edit]);
+
+        let title = match kind {
+            SymbolKind::Flag => format!(
+                "Add a trigger template setting flag `{}` to {}",
+                id, file_label
+            ),
+            _ => format!("Create {} `{}` in {}", kind_name(kind), id, file_label),
+        };
+        Some(CodeAction {
+            title,
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                ..WorkspaceEdit::default()
+            }),
+            command: None,
+            is_preferred: Some(true),
+            disabled: None,
+            data: None,
+        })
+    }
+
+    /// Picks the document holding the most definitions of `kind`, so stubs land next to
+    /// their siblings (e.g. rooms in `rooms.amble`).
+    fn stub_target_document(&self, kind: SymbolKind) -> Option<Url> {
+        let mut counts: HashMap<Url, usize> = HashMap::new();
+        for entry in self.symbols.index(kind).definitions_iter() {
+            *counts
+                .entry(entry.value().location.uri.clone())
+                .or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .max_by(|(uri_a, count_a), (uri_b, count_b)| {
+                count_a
+                    .cmp(count_b)
+                    .then_with(|| uri_b.as_str().cmp(uri_a.as_str()))
+            })
+            .map(|(uri, _)| uri)
+    }
+}
+
+fn replace_with_action(
+    uri: &Url,
+    range: Range,
+    replacement: &str,
+    preferred: bool,
+    diagnostic: &Diagnostic,
+) -> CodeAction {
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range,
+            new_text: replacement.to_string(),
+        }],
+    );
+
+    CodeAction {
+        title: format!("Replace with `{}`", replacement),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        }),
+        command: None,
+        is_preferred: Some(preferred),
+        disabled: None,
+        data: None,
+    }
+}
+
+/// Blank line between the existing content and the appended stub.
+fn stub_separator(text: &str) -> &'static str {
+    if text.trim().is_empty() || text.ends_with("\n\n") {
+        ""
+    } else if text.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    }
+}
+
+/// Mirrors the required fields of the `amble-*-block` snippets.
+fn definition_stub(kind: SymbolKind, id: &str) -> String {
+    let name = display_name_for_id(id);
+    match kind {
+        SymbolKind::Room => format!(
+            "room {id} {{\n    name \"{name}\"\n    desc \"TODO: describe {name}\"\n}}\n"
+        ),
+        SymbolKind::Item => format!(
+            "item {id} {{\n    name \"{name}\"\n    desc \"TODO: describe {name}\"\n    location nowhere \"TODO: place {id}\"\n}}\n"
+        ),
+        SymbolKind::Npc => format!(
+            "npc {id} {{\n    name \"{name}\"\n    desc \"TODO: describe {name}\"\n    max_hp 10\n    location nowhere \"TODO: place {id}\"\n}}\n"
+        ),
+        // Commented out: any live event would set the flag before the author chose when,
+        // bypassing every `has flag` / `missing flag` check on it.
+        _ => format!(
+            "# TODO: pick the event that sets flag {id}, then uncomment.\n\
+             # trigger \"Set {id}\" when <event> {{\n\
+             #     do add flag {id}\n\
+             # }}\n"
+        ),
+    }
+}
+
+/// Turns `old-forest_path` into `Old Forest Path` for the stub's `name`.
+fn display_name_for_id(id: &str) -> String {
+    id.split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree_sitter::Parser;
+
+    #[test]
+    fn stubs_parse_without_errors() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_amble::language())
+            .expect("load amble grammar");
+        // Stubs are appended to a file that already holds definitions.
+        let existing = "room hall {\n}\n\n";
+        for kind in [
+            SymbolKind::Room,
+            SymbolKind::Item,
+            SymbolKind::Npc,
+            SymbolKind::Flag,
+        ] {
+            let stub = definition_stub(kind, "old-forest_path");
+            let source = format!("{}{}", existing, stub);
+            let tree = parser.parse(&source, None).expect("parse stub");
+            assert!(!tree.root_node().has_error(), "bad stub: {}", stub);
+        }
+
+        // The flag stub must not change game logic until the author picks an event.
+        let stub = definition_stub(SymbolKind::Flag, "lamp-lit");
+        let source = format!("{}{}", existing, stub);
+        let tree = parser.parse(&source, None).expect("parse flag stub");
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let definitions: Vec<&str> = root
+            .named_children(&mut cursor)
+            .map(|child| child.kind())
+            .filter(|kind| *kind != "comment")
+            .collect();
+        assert_eq!(definitions, ["room_def"]);
+        assert!(stub.contains("#     do add flag lamp-lit"));
+    }
+
+    #[test]
+    fn stub_names_and_payload_round_trip() {
+        assert_eq!(display_name_for_id("old-forest_path"), "Old Forest Path");
+        assert_eq!(stub_separator("room a {}"), "\n\n");
+        assert_eq!(stub_separator("room a {}\n"), "\n");
+        assert_eq!(stub_separator(""), "");
+
+        let range = Range::default();
+        let data = undefined_symbol_data(SymbolKind::Npc, "bob", &[], range).expect("npc payload");
+        let parsed: UndefinedSymbol = serde_json::from_value(data).expect("round trip");
+        assert_eq!(kind_from_name(&parsed.kind), Some(SymbolKind::Npc));
+        assert_eq!(parsed.id, "bob");
+        assert!(parsed.suggestions.is_empty());
+        assert!(undefined_symbol_data(SymbolKind::Goal, "g", &[], range).is_none());
+
+        let suggestions = vec!["goal_a".to_string()];
+        let data = undefined_symbol_data(SymbolKind::Goal, "goal_b", &suggestions, range)
+            .expect("goal payload with suggestions");
+        let parsed: UndefinedSymbol = serde_json::from_value(data).expect("round trip");
+        assert_eq!(kind_from_name(&parsed.kind), Some(SymbolKind::Goal));
+        assert_eq!(parsed.suggestions, suggestions);
+    }
+}
@@ -0,0 +1,1258 @@
+use crate::analysis::{FlagUseKind, FlagUser};
+use crate::backend::Backend;
+use crate::symbols::{GoalCondition, GoalConditionKind, RoomExit, SymbolMetadata};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::{ExecuteCommandParams, Range, Url};
+
+pub(crate) const EXPORT_ROOM_GRAPH_COMMAND: &str = "amble.exportRoomGraph";
+pub(crate) const EXPORT_FLAG_GRAPH_COMMAND: &str = "amble.exportFlagGraph";
+pub(crate) const EXPORT_GOAL_GRAPH_COMMAND: &str = "amble.exportGoalGraph";
+
+const LOCKED_EXIT_COLOR: &str = "firebrick";
+const FLAG_REMOVE_COLOR: &str = "firebrick";
+const GOAL_PROBLEM_COLOR: &str = "firebrick";
+
+/// A graph `amble-lsp graph` and `workspace/executeCommand` can export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GraphKind {
+    Rooms,
+    Flags,
+    Goals,
+}
+
+impl GraphKind {
+    pub(crate) const ALL: [GraphKind; 3] = [GraphKind::Rooms, GraphKind::Flags, GraphKind::Goals];
+
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "rooms" => Some(GraphKind::Rooms),
+            "flags" => Some(GraphKind::Flags),
+            "goals" => Some(GraphKind::Goals),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn command(self) -> &'static str {
+        match self {
+            GraphKind::Rooms => EXPORT_ROOM_GRAPH_COMMAND,
+            GraphKind::Flags => EXPORT_FLAG_GRAPH_COMMAND,
+            GraphKind::Goals => EXPORT_GOAL_GRAPH_COMMAND,
+        }
+    }
+
+    fn from_command(command: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.command() == command)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl GraphFormat {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "dot" => Some(GraphFormat::Dot),
+            "mermaid" => Some(GraphFormat::Mermaid),
+            "json" => Some(GraphFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A defined room and the exits leading out of it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RoomNode {
+    pub id: String,
+    pub name: Option<String>,
+    pub exits: Vec<RoomExit>,
+}
+
+/// A flag and every place it is set, read or removed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FlagNode {
+    pub id: String,
+    pub sites: Vec<FlagSite>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FlagSite {
+    pub kind: FlagUseKind,
+    pub user: FlagUser,
+    /// Workspace-relative path of the file the use is in.
+    pub file: String,
+    pub range: Range,
+}
+
+/// A goal and the conditions that start, complete and fail it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GoalNode {
+    pub id: String,
+    pub name: Option<String>,
+    pub group: Option<String>,
+    pub start: Option<GoalCondition>,
+    pub done: Option<GoalCondition>,
+    pub fail: Option<GoalCondition>,
+    pub uri: Url,
+    pub range: Range,
+}
+
+impl GoalNode {
+    /// Goals without a `group` count as required.
+    pub(crate) fn is_required(&self) -> bool {
+        matches!(self.group.as_deref(), None | Some("required"))
+    }
+
+    /// `goal complete` conditions the goal waits on to start or finish.
+    pub(crate) fn prerequisites(&self) -> impl Iterator<Item = &GoalCondition> {
+        [&self.start, &self.done]
+            .into_iter()
+            .flatten()
+            .filter(|condition| condition.kind == GoalConditionKind::GoalComplete)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GoalGraph {
+    /// Sorted by id.
+    pub goals: Vec<GoalNode>,
+    /// Flags some trigger sets, directly or through an action set that gets run.
+    pub triggered_flags: HashSet<String>,
+}
+
+impl GoalGraph {
+    /// The shortest chain of prerequisites leading from `goal` back to itself, starting with
+    /// `goal`.
+    pub(crate) fn cycle_through(&self, goal: &str) -> Option<Vec<String>> {
+        let prerequisites: HashMap<&str, Vec<&str>> = self
+            .goals
+            .iter()
+            .map(|node| {
+                let ids = node.prerequisites().map(|condition| condition.id.as_str());
+                (node.id.as_str(), ids.collect())
+            })
+            .collect();
+
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from([goal]);
+        while let Some(current) = queue.pop_front() {
+            for &next in prerequisites.get(current).into_iter().flatten() {
+                if next == goal {
+                    let mut cycle = vec![current.to_string()];
+                    let mut step = current;
+                    while step != goal {
+                        step = previous[step];
+                        cycle.push(step.to_string());
+                    }
+                    cycle.reverse();
+                    return Some(cycle);
+                }
+                if !previous.contains_key(next) {
+                    previous.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Every distinct prerequisite loop, each starting from its smallest goal id.
+    pub(crate) fn cycles(&self) -> Vec<Vec<String>> {
+        let cycles: BTreeSet<Vec<String>> = self
+            .goals
+            .iter()
+            .filter_map(|goal| self.cycle_through(&goal.id))
+            .map(|mut cycle| {
+                let smallest = (0..cycle.len())
+                    .min_by_key(|&index| &cycle[index])
+                    .unwrap_or(0);
+                cycle.rotate_left(smallest);
+                cycle
+            })
+            .collect();
+        cycles.into_iter().collect()
+    }
+}
+
+/// Workspace-wide graphs some diagnostics read, built once per batch of documents rather than
+/// once per document.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WorkspaceGraphs {
+    pub goals: GoalGraph,
+    /// Rooms reachable from the player start; `None` without a valid start.
+    pub reachable_rooms: Option<HashSet<String>>,
+}
+
+impl Backend {
+    pub(crate) fn workspace_graphs(&self) -> WorkspaceGraphs {
+        WorkspaceGraphs {
+            goals: self.goal_graph(),
+            reachable_rooms: self.reachable_rooms(),
+        }
+    }
+
+    /// Renders `kind` as text in `format`.
+    pub(crate) fn export_graph(&self, kind: GraphKind, format: GraphFormat) -> String {
+        match kind {
+            GraphKind::Rooms => render_room_graph(&self.room_graph(), format),
+            GraphKind::Flags => render_flag_graph(&self.flag_graph(), format),
+            GraphKind::Goals => render_goal_graph(&self.goal_graph(), format),
+        }
+    }
+
+    pub(crate) fn goal_graph(&self) -> GoalGraph {
+        let mut goals: Vec<GoalNode> = self
+            .symbols
+            .goals
+            .definitions_iter()
+            .filter_map(|entry| {
+                let SymbolMetadata::Goal(meta) = &entry.value().metadata else {
+                    return None;
+                };
+                Some(GoalNode {
+                    id: entry.key().clone(),
+                    name: meta.name.clone(),
+                    group: meta.group.clone(),
+                    start: meta.start.clone(),
+                    done: meta.done.clone(),
+                    fail: meta.fail.clone(),
+                    uri: entry.value().location.uri.clone(),
+                    range: entry.value().location.range,
+                })
+            })
+            .collect();
+        goals.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let run_action_sets: HashSet<String> = self
+            .symbols
+            .action_sets
+            .references_iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        let mut triggered_flags = HashSet::new();
+        for entry in self.flag_uses.iter() {
+            for flag_use in entry.value() {
+                if flag_use.kind != FlagUseKind::Set {
+                    continue;
+                }
+                let triggered = match &flag_use.user {
+                    FlagUser::Trigger(_) => true,
+                    FlagUser::ActionSet(name) => run_action_sets.contains(name),
+                    _ => false,
+                };
+                if triggered {
+                    triggered_flags.insert(flag_use.flag.clone());
+                }
+            }
+        }
+
+        GoalGraph {
+            goals,
+            triggered_flags,
+        }
+    }
+
+    /// Every defined room, sorted by id so exports are stable.
+    pub(crate) fn room_graph(&self) -> Vec<RoomNode> {
+        let mut rooms: Vec<RoomNode> = self
+            .symbols
+            .rooms
+            .definitions_iter()
+            .map(|entry| {
+                let (name, exits) = match &entry.value().metadata {
+                    SymbolMetadata::Room(meta) => (meta.name.clone(), meta.exits.clone()),
+                    _ => (None, Vec::new()),
+                };
+                RoomNode {
+                    id: entry.key().clone(),
+                    name,
+                    exits,
+                }
+            })
+            .collect();
+        rooms.sort_by(|a, b| a.id.cmp(&b.id));
+        rooms
+    }
+
+    /// Every flag that is set, read or removed anywhere, sorted by id; each flag's uses are
+    /// ordered setters first, then readers, then removers.
+    pub(crate) fn flag_graph(&self) -> Vec<FlagNode> {
+        let mut flags: BTreeMap<String, Vec<FlagSite>> = BTreeMap::new();
+        for entry in self.flag_uses.iter() {
+            let Ok(uri) = Url::parse(entry.key()) else {
+                continue;
+            };
+            let file = self
+                .definition_display_path(&uri)
+                .unwrap_or_else(|| uri.to_string());
+            for flag_use in entry.value() {
+                flags
+                    .entry(flag_use.flag.clone())
+                    .or_default()
+                    .push(FlagSite {
+                        kind: flag_use.kind,
+                        user: flag_use.user.clone(),
+                        file: file.clone(),
+                        range: flag_use.range,
+                    });
+            }
+        }
+
+        flags
+            .into_iter()
+            .map(|(id, mut sites)| {
+                sites.sort_by(|a, b| {
+                    (a.kind, &a.file, a.range.start.line, a.range.start.character).cmp(&(
+                        b.kind,
+                        &b.file,
+                        b.range.start.line,
+                        b.range.start.character,
+                    ))
+                });
+                FlagNode { id, sites }
+            })
+            .collect()
+    }
+
+    /// Handles the `amble.export*Graph` commands. The optional argument is an object with a
+    /// `format` (defaults to `dot`) and an `output` path to also write the graph to; the
+    /// rendered graph is returned either way.
+    pub(crate) fn execute_graph_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<Value>> {
+        let Some(kind) = GraphKind::from_command(&params.command) else {
+            return Err(Error::method_not_found());
+        };
+        let options = params.arguments.first();
+        let format = match options.and_then(|options| options.get("format")) {
+            None | Some(Value::Null) => GraphFormat::Dot,
+            Some(value) => value
+                .as_str()
+                .and_then(GraphFormat::parse)
+                .ok_or_else(|| Error::invalid_params(format!("unknown graph format {}", value)))?,
+        };
+
+        let graph = self.export_graph(kind, format);
+        if let Some(output) = options
+            .and_then(|options| options.get("output"))
+            .and_then(Value::as_str)
+        {
+            std::fs::write(output, &graph).map_err(|error| {
+                let mut rpc_error = Error::internal_error();
+                rpc_error.message = format!("could not write {}: {}", output, error).into();
+                rpc_error
+            })?;
+        }
+        Ok(Some(Value::String(graph)))
+    }
+}
+
+pub(crate) fn render_room_graph(rooms: &[RoomNode], format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => room_graph_dot(rooms),
+        GraphFormat::Mermaid => room_graph_mermaid(rooms),
+        GraphFormat::Json => format!("{:#}\n", room_graph_json(rooms)),
+    }
+}
+
+pub(crate) fn render_goal_graph(graph: &GoalGraph, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => goal_graph_dot(graph),
+        GraphFormat::Mermaid => goal_graph_mermaid(graph),
+        GraphFormat::Json => format!("{:#}\n", goal_graph_json(graph)),
+    }
+}
+
+pub(crate) fn render_flag_graph(flags: &[FlagNode], format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => flag_graph_dot(flags),
+        GraphFormat::Mermaid => flag_graph_mermaid(flags),
+        GraphFormat::Json => format!("{:#}\n", flag_graph_json(flags)),
+    }
+}
+
+/// Room ids mapped to their labels, including exit targets that are never defined.
+fn room_labels(rooms: &[RoomNode]) -> BTreeMap<&str, &str> {
+    let mut labels = BTreeMap::new();
+    for room in rooms {
+        labels.insert(room.id.as_str(), room.name.as_deref().unwrap_or(&room.id));
+    }
+    for exit in rooms.iter().flat_map(|room| &room.exits) {
+        labels.entry(exit.to.as_str()).or_insert(exit.to.as_str());
+    }
+    labels
+}
+
+/// The direction, then one line per restriction on the exit.
+fn exit_label_lines(exit: &RoomExit) -> Vec<String> {
+    let mut lines = vec![exit.direction.clone()];
+    if exit.locked {
+        lines.push("locked".to_string());
+    }
+    if exit.barred.is_some() {
+        lines.push("barred".to_string());
+    }
+    if !exit.required_flags.is_empty() {
+        lines.push(format!("flags: {}", exit.required_flags.join(", ")));
+    }
+    if !exit.required_items.is_empty() {
+        lines.push(format!("items: {}", exit.required_items.join(", ")));
+    }
+    lines
+}
+
+fn room_graph_dot(rooms: &[RoomNode]) -> String {
+    let mut out = String::from("digraph rooms {\n    node [shape=box];\n");
+    for (id, label) in room_labels(rooms) {
+        let _ = writeln!(out, "    {} [label={}];", dot_string(id), dot_string(label));
+    }
+    for room in rooms {
+        for exit in &room.exits {
+            let mut attributes = vec![format!(
+                "label={}",
+                dot_string(&exit_label_lines(exit).join("\n"))
+            )];
+            if exit.hidden {
+                attributes.push("style=dashed".to_string());
+            }
+            if exit.locked {
+                attributes.push(format!("color={}", LOCKED_EXIT_COLOR));
+                attributes.push(format!("fontcolor={}", LOCKED_EXIT_COLOR));
+            }
+            if let Some(message) = &exit.barred {
+                attributes.push(format!("tooltip={}", dot_string(message)));
+            }
+            let _ = writeln!(
+                out,
+                "    {} -> {} [{}];",
+                dot_string(&room.id),
+                dot_string(&exit.to),
+                attributes.join(", ")
+            );
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn room_graph_json(rooms: &[RoomNode]) -> Value {
+    let rooms: Vec<Value> = rooms
+        .iter()
+        .map(|room| {
+            let exits: Vec<Value> = room
+                .exits
+                .iter()
+                .map(|exit| {
+                    json!({
+                        "direction": exit.direction,
+                        "to": exit.to,
+                        "hidden": exit.hidden,
+                        "locked": exit.locked,
+                        "barred": exit.barred,
+                        "requiredFlags": exit.required_flags,
+                        "requiredItems": exit.required_items,
+                    })
+                })
+                .collect();
+            json!({ "id": room.id, "name": room.name, "exits": exits })
+        })
+        .collect();
+    json!({ "rooms": rooms })
+}
+
+/// Setters point at the flag and the flag points at its readers, so prerequisite chains read
+/// left to right; removals are dashed.
+fn flag_graph_dot(flags: &[FlagNode]) -> String {
+    let mut out = String::from("digraph flags {\n    rankdir=LR;\n    node [shape=box];\n");
+    for flag in flags {
+        let _ = writeln!(
+            out,
+            "    {} [label={}, shape=ellipse];",
+            dot_string(&flag_node_key(&flag.id)),
+            dot_string(&flag.id)
+        );
+    }
+    for user in flag_users(flags) {
+        let _ = writeln!(
+            out,
+            "    {} [label={}];",
+            dot_string(&user_node_key(user)),
+            dot_string(&user_label(user))
+        );
+    }
+    for (flag, kind, user) in flag_edges(flags) {
+        let flag = dot_string(&flag_node_key(flag));
+        let user = dot_string(&user_node_key(user));
+        let _ = match kind {
+            FlagUseKind::Set => writeln!(out, "    {} -> {} [label=\"sets\"];", user, flag),
+            FlagUseKind::Read => writeln!(out, "    {} -> {} [label=\"reads\"];", flag, user),
+            FlagUseKind::Remove => writeln!(
+                out,
+                "    {} -> {} [label=\"removes\", style=dashed, color={}, fontcolor={}];",
+                user, flag, FLAG_REMOVE_COLOR, FLAG_REMOVE_COLOR
+            ),
+        };
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Flags are numbered `f0`, `f1`... and their users `u0`, `u1`..., both in sorted order.
+fn flag_graph_mermaid(flags: &[FlagNode]) -> String {
+    let flag_ids: BTreeMap<&str, String> = flags
+        .iter()
+        .enumerate()
+        .map(|(index, flag)| (flag.id.as_str(), format!("f{}", index)))
+        .collect();
+    let users = flag_users(flags);
+    let user_ids: BTreeMap<&FlagUser, String> = users
+        .iter()
+        .enumerate()
+        .map(|(index, user)| (*user, format!("u{}", index)))
+        .collect();
+
+    let mut out = String::from("flowchart LR\n");
+    for flag in flags {
+        let _ = writeln!(
+            out,
+            "    {}([\"{}\"])",
+            flag_ids[flag.id.as_str()],
+            mermaid_text(&flag.id)
+        );
+    }
+    for user in &users {
+        let _ = writeln!(
+            out,
+            "    {}[\"{}\"]",
+            user_ids[user],
+            mermaid_text(&user_label(user))
+        );
+    }
+    for (flag, kind, user) in flag_edges(flags) {
+        let flag = &flag_ids[flag];
+        let user = &user_ids[user];
+        let _ = match kind {
+            FlagUseKind::Set => writeln!(out, "    {} -->|sets| {}", user, flag),
+            FlagUseKind::Read => writeln!(out, "    {} -->|reads| {}", flag, user),
+            FlagUseKind::Remove => writeln!(out, "    {} -.->|removes| {}", user, flag),
+        };
+    }
+    out
+}
+
+fn flag_graph_json(flags: &[FlagNode]) -> Value {
+    let sites = |flag: &FlagNode, kind: FlagUseKind| -> Vec<Value> {
+        flag.sites
+            .iter()
+            .filter(|site| site.kind == kind)
+            .map(|site| {
+                let mut value = user_json(&site.user);
+                value["file"] = json!(site.file);
+                value["range"] = json!(site.range);
+                value
+            })
+            .collect()
+    };
+    let flags: Vec<Value> = flags
+        .iter()
+        .map(|flag| {
+            json!({
+                "id": flag.id,
+                "setters": sites(flag, FlagUseKind::Set),
+                "readers": sites(flag, FlagUseKind::Read),
+                "removers": sites(flag, FlagUseKind::Remove),
+            })
+        })
+        .collect();
+    json!({ "flags": flags })
+}
+
+/// Each distinct user of any flag, sorted.
+fn flag_users(flags: &[FlagNode]) -> Vec<&FlagUser> {
+    let users: BTreeSet<&FlagUser> = flags
+        .iter()
+        .flat_map(|flag| flag.sites.iter().map(|site| &site.user))
+        .collect();
+    users.into_iter().collect()
+}
+
+/// One edge per flag, kind of use and user, however many times the user repeats it.
+fn flag_edges(flags: &[FlagNode]) -> BTreeSet<(&str, FlagUseKind, &FlagUser)> {
+    flags
+        .iter()
+        .flat_map(|flag| {
+            flag.sites
+                .iter()
+                .map(|site| (flag.id.as_str(), site.kind, &site.user))
+        })
+        .collect()
+}
+
+fn flag_node_key(flag: &str) -> String {
+    format!("flag:{}", flag)
+}
+
+fn user_node_key(user: &FlagUser) -> String {
+    match user {
+        FlagUser::Trigger(name) => format!("trigger:{}", name),
+        FlagUser::ActionSet(name) => format!("action-set:{}", name),
+        FlagUser::Condition(name) => format!("condition:{}", name),
+        FlagUser::Goal(name) => format!("goal:{}", name),
+        FlagUser::Exit { room, direction } => format!("exit:{}:{}", room, direction),
+        FlagUser::Room(room) => format!("room:{}", room),
+    }
+}
+
+fn user_label(user: &FlagUser) -> String {
+    match user {
+        FlagUser::Trigger(name) => format!("trigger \"{}\"", name),
+        FlagUser::ActionSet(name) => format!("action set {}", name),
+        FlagUser::Condition(name) => format!("condition {}", name),
+        FlagUser::Goal(name) => format!("goal {}", name),
+        FlagUser::Exit { room, direction } => format!("exit {} {}", room, direction),
+        FlagUser::Room(room) => format!("room {} overlay", room),
+    }
+}
+
+fn user_json(user: &FlagUser) -> Value {
+    match user {
+        FlagUser::Trigger(name) => json!({ "kind": "trigger", "name": name }),
+        FlagUser::ActionSet(name) => json!({ "kind": "actionSet", "name": name }),
+        FlagUser::Condition(name) => json!({ "kind": "condition", "name": name }),
+        FlagUser::Goal(name) => json!({ "kind": "goal", "name": name }),
+        FlagUser::Exit { room, direction } => {
+            json!({ "kind": "exit", "room": room, "direction": direction })
+        }
+        FlagUser::Room(room) => json!({ "kind": "overlay", "room": room }),
+    }
+}
+
+/// One edge of the goal graph: a prerequisite goal or a flag, pointing at the goal whose
+/// `start`, `done` or `fail` condition names it.
+struct GoalEdge<'a> {
+    from: GoalEdgeSource<'a>,
+    to: &'a str,
+    label: String,
+    /// Part of a prerequisite loop.
+    in_cycle: bool,
+}
+
+enum GoalEdgeSource<'a> {
+    Goal(&'a str),
+    Flag(&'a str),
+}
+
+fn goal_edges(graph: &GoalGraph) -> Vec<GoalEdge<'_>> {
+    // (goal, prerequisite) pairs along each loop.
+    let mut cycle_links: HashSet<(String, String)> = HashSet::new();
+    for cycle in graph.cycles() {
+        for (index, goal) in cycle.iter().enumerate() {
+            let prerequisite = &cycle[(index + 1) % cycle.len()];
+            cycle_links.insert((goal.clone(), prerequisite.clone()));
+        }
+    }
+
+    let mut edges = Vec::new();
+    for goal in &graph.goals {
+        let conditions = [
+            ("start", &goal.start),
+            ("done", &goal.done),
+            ("fail", &goal.fail),
+        ];
+        for (stage, condition) in conditions {
+            let Some(condition) = condition else {
+                continue;
+            };
+            let (from, label) = match condition.kind {
+                GoalConditionKind::GoalComplete => {
+                    (GoalEdgeSource::Goal(&condition.id), stage.to_string())
+                }
+                GoalConditionKind::HasFlag => {
+                    (GoalEdgeSource::Flag(&condition.id), stage.to_string())
+                }
+                GoalConditionKind::MissingFlag => (
+                    GoalEdgeSource::Flag(&condition.id),
+                    format!("{} (missing)", stage),
+                ),
+                GoalConditionKind::FlagInProgress => (
+                    GoalEdgeSource::Flag(&condition.id),
+                    format!("{} (in progress)", stage),
+                ),
+                GoalConditionKind::FlagComplete => (
+                    GoalEdgeSource::Flag(&condition.id),
+                    format!("{} (complete)", stage),
+                ),
+                GoalConditionKind::HasItem | GoalConditionKind::ReachedRoom => continue,
+            };
+            let in_cycle = condition.kind == GoalConditionKind::GoalComplete
+                && stage != "fail"
+                && cycle_links.contains(&(goal.id.clone(), condition.id.clone()));
+            edges.push(GoalEdge {
+                from,
+                to: &goal.id,
+                label,
+                in_cycle,
+            });
+        }
+    }
+    edges
+}
+
+/// Flags named by goal conditions, sorted.
+fn goal_flags<'a>(edges: &[GoalEdge<'a>]) -> BTreeSet<&'a str> {
+    edges
+        .iter()
+        .filter_map(|edge| match edge.from {
+            GoalEdgeSource::Flag(flag) => Some(flag),
+            GoalEdgeSource::Goal(_) => None,
+        })
+        .collect()
+}
+
+fn goal_label(goal: &GoalNode) -> String {
+    match &goal.name {
+        Some(name) => format!("{}\n({})", name, goal.id),
+        None => goal.id.clone(),
+    }
+}
+
+/// Prerequisites and flags point at the goals waiting on them. Optional goals are dashed;
+/// loops and flags no trigger sets are highlighted.
+fn goal_graph_dot(graph: &GoalGraph) -> String {
+    let edges = goal_edges(graph);
+    let mut out = String::from("digraph goals {\n    rankdir=LR;\n    node [shape=box];\n");
+    for goal in &graph.goals {
+        let style = if goal.is_required() {
+            ""
+        } else {
+            ", style=dashed"
+        };
+        let _ = writeln!(
+            out,
+            "    {} [label={}{}];",
+            dot_string(&format!("goal:{}", goal.id)),
+            dot_string(&goal_label(goal)),
+            style
+        );
+    }
+    for flag in goal_flags(&edges) {
+        let color = if graph.triggered_flags.contains(flag) {
+            String::new()
+        } else {
+            format!(
+                ", color={}, fontcolor={}",
+                GOAL_PROBLEM_COLOR, GOAL_PROBLEM_COLOR
+            )
+        };
+        let _ = writeln!(
+            out,
+            "    {} [label={}, shape=ellipse{}];",
+            dot_string(&flag_node_key(flag)),
+            dot_string(flag),
+            color
+        );
+    }
+    for edge in &edges {
+        let from = match edge.from {
+            GoalEdgeSource::Goal(goal) => format!("goal:{}", goal),
+            GoalEdgeSource::Flag(flag) => flag_node_key(flag),
+        };
+        let color = if edge.in_cycle {
+            format!(
+                ", color={}, fontcolor={}",
+                GOAL_PROBLEM_COLOR, GOAL_PROBLEM_COLOR
+            )
+        } else {
+            String::new()
+        };
+        let _ = writeln!(
+            out,
+            "    {} -> {} [label={}{}];",
+            dot_string(&from),
+            dot_string(&format!("goal:{}", edge.to)),
+            dot_string(&edge.label),
+            color
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Goals are numbered `g0`, `g1`... and flags `f0`, `f1`..., both in sorted order.
+fn goal_graph_mermaid(graph: &GoalGraph) -> String {
+    let edges = goal_edges(graph);
+    let mut node_ids: HashMap<String, String> = HashMap::new();
+    let mut out = String::from("flowchart LR\n");
+    for (index, goal) in graph.goals.iter().enumerate() {
+        let id = format!("g{}", index);
+        let _ = writeln!(
+            out,
+            "    {}[\"{}\"]",
+            id,
+            mermaid_text(&goal_label(goal)).replace('\n', "<br/>")
+        );
+        node_ids.insert(format!("goal:{}", goal.id), id);
+    }
+    for (index, flag) in goal_flags(&edges).into_iter().enumerate() {
+        let id = format!("f{}", index);
+        let _ = writeln!(out, "    {}([\"{}\"])", id, mermaid_text(flag));
+        node_ids.insert(flag_node_key(flag), id);
+    }
+
+    let mut cycle_links = Vec::new();
+    let mut link_index = 0;
+    for edge in &edges {
+        let from = match edge.from {
+            GoalEdgeSource::Goal(goal) => format!("goal:{}", goal),
+            GoalEdgeSource::Flag(flag) => flag_node_key(flag),
+        };
+        // Prerequisites on goals that are never defined have no node to point from.
+        let (Some(from), Some(to)) = (
+            node_ids.get(&from),
+            node_ids.get(&format!("goal:{}", edge.to)),
+        ) else {
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "    {} -->|\"{}\"| {}",
+            from,
+            mermaid_text(&edge.label),
+            to
+        );
+        if edge.in_cycle {
+            cycle_links.push(link_index.to_string());
+        }
+        link_index += 1;
+    }
+    if !cycle_links.is_empty() {
+        let _ = writeln!(
+            out,
+            "    linkStyle {} stroke:{},color:{}",
+            cycle_links.join(","),
+            GOAL_PROBLEM_COLOR,
+            GOAL_PROBLEM_COLOR
+        );
+    }
+    out
+}
+
+fn goal_graph_json(graph: &GoalGraph) -> Value {
+    let condition_json = |condition: &Option<GoalCondition>| -> Value {
+        let Some(condition) = condition else {
+            return Value::Null;
+        };
+        let kind = match condition.kind {
+            GoalConditionKind::HasFlag => "hasFlag",
+            GoalConditionKind::MissingFlag => "missingFlag",
+            GoalConditionKind::HasItem => "hasItem",
+            GoalConditionKind::ReachedRoom => "reachedRoom",
+            GoalConditionKind::GoalComplete => "goalComplete",
+            GoalConditionKind::FlagInProgress => "flagInProgress",
+            GoalConditionKind::FlagComplete => "flagComplete",
+        };
+        json!({ "kind": kind, "id": condition.id })
+    };
+    let goals: Vec<Value> = graph
+        .goals
+        .iter()
+        .map(|goal| {
+            json!({
+                "id": goal.id,
+                "name": goal.name,
+                "group": goal.group,
+                "start": condition_json(&goal.start),
+                "done": condition_json(&goal.done),
+                "fail": condition_json(&goal.fail),
+            })
+        })
+        .collect();
+    let edges = goal_edges(graph);
+    let untriggered_flags: Vec<&str> = goal_flags(&edges)
+        .into_iter()
+        .filter(|flag| !graph.triggered_flags.contains(*flag))
+        .collect();
+
+    json!({
+        "goals": goals,
+        "cycles": graph.cycles(),
+        "untriggeredFlags": untriggered_flags,
+    })
+}
+
+fn dot_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Room ids aren't valid Mermaid node ids (`-` is an edge), so nodes are numbered `r0`, `r1`...
+/// in id order and labelled with the display name.
+fn room_graph_mermaid(rooms: &[RoomNode]) -> String {
+    let labels = room_labels(rooms);
+    let node_ids: BTreeMap<&str, String> = labels
+        .keys()
+        .enumerate()
+        .map(|(index, id)| (*id, format!("r{}", index)))
+        .collect();
+
+    let mut out = String::from("flowchart LR\n");
+    for (id, label) in &labels {
+        let _ = writeln!(out, "    {}[\"{}\"]", node_ids[id], mermaid_text(label));
+    }
+
+    let mut locked_links = Vec::new();
+    let mut link_index = 0;
+    for room in rooms {
+        for exit in &room.exits {
+            let arrow = if exit.hidden { "-.->" } else { "-->" };
+            let label = exit_label_lines(exit)
+                .iter()
+                .map(|line| mermaid_text(line))
+                .collect::<Vec<_>>()
+                .join("<br/>");
+            let _ = writeln!(
+                out,
+                "    {} {}|\"{}\"| {}",
+                node_ids[room.id.as_str()],
+                arrow,
+                label,
+                node_ids[exit.to.as_str()]
+            );
+            if exit.locked {
+                locked_links.push(link_index.to_string());
+            }
+            link_index += 1;
+        }
+    }
+    if !locked_links.is_empty() {
+        let _ = writeln!(
+            out,
+            "    linkStyle {} stroke:{},color:{}",
+            locked_links.join(","),
+            LOCKED_EXIT_COLOR,
+            LOCKED_EXIT_COLOR
+        );
+    }
+    out
+}
+
+fn mermaid_text(value: &str) -> String {
+    value
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::NumberOrString;
+    use tower_lsp::LspService;
+
+    const ROOMS: &str = r#"
+room hall {
+    name "Great \"Hall\""
+    desc "A hall."
+    exit north -> porch
+    exit down -> cellar {
+        hidden,
+        locked,
+        required_items(lamp),
+        required_flags(has_key),
+        barred "It's too dark."
+    }
+}
+
+room porch {
+    name "Porch"
+    desc "A porch."
+    exit south -> hall
+}
+"#;
+
+    const TRIGGERS: &str = r#"
+trigger "Clean plaque" when always {
+    do add flag cleaned-plaque
+    do advance flag quest
+}
+
+trigger "Read plaque" when always {
+    if has flag cleaned-plaque {
+        do remove flag cleaned-plaque
+    }
+    if has flag quest#2 {
+        do show "Done."
+    }
+}
+"#;
+
+    fn rooms() -> Vec<RoomNode> {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = Url::parse("file:///world/rooms.amble").unwrap();
+        backend.analyze_document(&uri, ROOMS);
+        backend.room_graph()
+    }
+
+    #[test]
+    fn room_graph_records_exit_options() {
+        let rooms = rooms();
+        assert_eq!(
+            rooms
+                .iter()
+                .map(|room| room.id.as_str())
+                .collect::<Vec<_>>(),
+            ["hall", "porch"]
+        );
+        assert_eq!(
+            rooms[0].exits[1],
+            RoomExit {
+                direction: "down".to_string(),
+                to: "cellar".to_string(),
+                hidden: true,
+                locked: true,
+                barred: Some("It's too dark.".to_string()),
+                required_flags: vec!["has_key".to_string()],
+                required_items: vec!["lamp".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn dot_styles_hidden_and_locked_exits() {
+        let dot = render_room_graph(&rooms(), GraphFormat::Dot);
+        assert!(dot.contains(r#""hall" [label="Great \"Hall\""];"#));
+        assert!(dot.contains(r#""cellar" [label="cellar"];"#));
+        assert!(dot.contains(r#""hall" -> "porch" [label="north"];"#));
+        assert!(dot.contains(
+            r#""hall" -> "cellar" [label="down\nlocked\nbarred\nflags: has_key\nitems: lamp", style=dashed, color=firebrick, fontcolor=firebrick, tooltip="It's too dark."];"#
+        ));
+    }
+
+    #[test]
+    fn mermaid_numbers_nodes_and_styles_locked_links() {
+        let mermaid = render_room_graph(&rooms(), GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("r1[\"Great #quot;Hall#quot;\"]"));
+        assert!(mermaid.contains("r1 -->|\"north\"| r2"));
+        assert!(mermaid.contains("r1 -.->|\"down<br/>locked"));
+        assert!(mermaid.contains("r2 -->|\"south\"| r1"));
+        assert!(mermaid.contains("linkStyle 1 stroke:firebrick,color:firebrick"));
+    }
+
+    fn flags() -> Vec<FlagNode> {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        backend.analyze_document(&Url::parse("file:///world/rooms.amble").unwrap(), ROOMS);
+        backend.analyze_document(
+            &Url::parse("file:///world/triggers.amble").unwrap(),
+            TRIGGERS,
+        );
+        backend.flag_graph()
+    }
+
+    #[test]
+    fn flag_graph_groups_setters_readers_and_removers() {
+        let flags = flags();
+        assert_eq!(
+            flags
+                .iter()
+                .map(|flag| flag.id.as_str())
+                .collect::<Vec<_>>(),
+            ["cleaned-plaque", "has_key", "quest"]
+        );
+
+        let uses = |index: usize| -> Vec<(FlagUseKind, FlagUser)> {
+            flags[index]
+                .sites
+                .iter()
+                .map(|site| (site.kind, site.user.clone()))
+                .collect()
+        };
+        assert_eq!(
+            uses(0),
+            [
+                (
+                    FlagUseKind::Set,
+                    FlagUser::Trigger("Clean plaque".to_string())
+                ),
+                (
+                    FlagUseKind::Read,
+                    FlagUser::Trigger("Read plaque".to_string())
+                ),
+                (
+                    FlagUseKind::Remove,
+                    FlagUser::Trigger("Read plaque".to_string())
+                ),
+            ]
+        );
+        assert_eq!(
+            uses(1),
+            [(
+                FlagUseKind::Read,
+                FlagUser::Exit {
+                    room: "hall".to_string(),
+                    direction: "down".to_string(),
+                }
+            )]
+        );
+        assert_eq!(uses(2).len(), 2);
+    }
+
+    #[test]
+    fn flag_graph_renders_dot_and_json() {
+        let flags = flags();
+        let dot = render_flag_graph(&flags, GraphFormat::Dot);
+        assert!(dot.contains(r#""trigger:Clean plaque" -> "flag:cleaned-plaque" [label="sets"];"#));
+        assert!(dot.contains(r#""flag:cleaned-plaque" -> "trigger:Read plaque" [label="reads"];"#));
+        assert!(dot.contains(r#""flag:has_key" -> "exit:hall:down" [label="reads"];"#));
+        assert!(dot.contains(
+            r#""trigger:Read plaque" -> "flag:cleaned-plaque" [label="removes", style=dashed"#
+        ));
+
+        let json: Value =
+            serde_json::from_str(&render_flag_graph(&flags, GraphFormat::Json)).unwrap();
+        let quest = &json["flags"][2];
+        assert_eq!(quest["id"], "quest");
+        assert_eq!(quest["setters"][0]["name"], "Clean plaque");
+        assert_eq!(quest["readers"][0]["file"], "/world/triggers.amble");
+        assert_eq!(quest["removers"].as_array().map(Vec::len), Some(0));
+    }
+
+    const GOALS: &str = r#"
+goal find-office {
+    name "Find the Office"
+    group required
+    start when goal complete get-invited
+    done when has flag checked-in
+}
+
+goal get-invited {
+    group required
+    start when goal complete find-office
+    done when has flag cleaned-plaque
+}
+
+goal read-plaque {
+    group optional
+    done when has flag never-set
+}
+"#;
+
+    fn goal_backend() -> (LspService<Backend>, Url) {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        backend.analyze_document(
+            &Url::parse("file:///world/triggers.amble").unwrap(),
+            TRIGGERS,
+        );
+        let goals = Url::parse("file:///world/goals.amble").unwrap();
+        backend.analyze_document(&goals, GOALS);
+        (service, goals)
+    }
+
+    #[test]
+    fn goal_graph_finds_cycles_and_triggered_flags() {
+        let (service, _) = goal_backend();
+        let graph = service.inner().goal_graph();
+
+        assert_eq!(
+            graph.cycle_through("get-invited"),
+            Some(vec!["get-invited".to_string(), "find-office".to_string()])
+        );
+        assert_eq!(graph.cycle_through("read-plaque"), None);
+        assert_eq!(
+            graph.cycles(),
+            vec![vec!["find-office".to_string(), "get-invited".to_string()]]
+        );
+        assert!(graph.triggered_flags.contains("cleaned-plaque"));
+        assert!(!graph.triggered_flags.contains("checked-in"));
+
+        let dot = render_goal_graph(&graph, GraphFormat::Dot);
+        assert!(dot.contains(r#""goal:find-office" [label="Find the Office\n(find-office)"];"#));
+        assert!(dot.contains(r#""goal:read-plaque" [label="read-plaque", style=dashed];"#));
+        assert!(dot
+            .contains(r#""flag:checked-in" [label="checked-in", shape=ellipse, color=firebrick"#));
+        assert!(dot.contains(
+            r#""goal:get-invited" -> "goal:find-office" [label="start", color=firebrick"#
+        ));
+        assert!(dot.contains(r#""flag:cleaned-plaque" -> "goal:get-invited" [label="done"];"#));
+    }
+
+    #[test]
+    fn goal_diagnostics_report_cycles_and_unset_done_flags() {
+        let (service, goals) = goal_backend();
+        let backend = service.inner();
+        let diagnostics = backend
+            .collect_diagnostics(&goals, &backend.workspace_graphs())
+            .unwrap();
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                matches!(
+                    &diagnostic.code,
+                    Some(NumberOrString::String(code))
+                        if code == "goal-dependency-cycle" || code == "goal-flag-never-set"
+                )
+            })
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                "Goal 'find-office' depends on itself: find-office -> get-invited -> find-office",
+                "Required goal 'find-office' can never be completed: no trigger sets flag 'checked-in'",
+                "Goal 'get-invited' depends on itself: get-invited -> find-office -> get-invited",
+            ]
+        );
+    }
+
+    #[test]
+    fn goal_documents_are_requeued_when_setters_or_prerequisites_change() {
+        let (service, goals) = goal_backend();
+        let backend = service.inner();
+
+        // Moving `add flag` from an action set nobody runs into a trigger changes no ids.
+        let triggers = Url::parse("file:///world/check-in.amble").unwrap();
+        let check_in = |steps: &str, trigger: &str| {
+            format!(
+                "let actions check_in_steps = {{\n    {}\n}}\n\
+                 trigger \"Check in\" when always {{\n    {}\n}}\n",
+                steps, trigger
+            )
+        };
+        backend.analyze_document(
+            &triggers,
+            &check_in("do add flag checked-in", "do show \"Hi.\""),
+        );
+        backend.pending_diagnostics.clear();
+        backend.analyze_document(
+            &triggers,
+            &check_in("do show \"Hi.\"", "do add flag checked-in"),
+        );
+        assert!(backend.pending_diagnostics.contains(&goals.to_string()));
+
+        let side = Url::parse("file:///world/side.amble").unwrap();
+        backend.analyze_document(
+            &side,
+            "goal side {\n    start when goal complete find-office\n}\n",
+        );
+        backend.pending_diagnostics.clear();
+        backend.analyze_document(
+            &side,
+            "goal side {\n    fail when goal complete find-office\n}\n",
+        );
+        assert!(backend.pending_diagnostics.contains(&goals.to_string()));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::analysis::amble_files_under;
+use crate::backend::{amble_parser, Backend};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressCreateParams, WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+
+const INDEXING_PROGRESS_TOKEN: &str = "amble-workspace-indexing";
+
+impl Backend {
+    /// Indexes every `.amble` file under the workspace roots, reporting `$/progress` while it
+    /// runs, then publishes diagnostics for the whole workspace.
+    pub(crate) async fn index_workspace(&self) {
+        let files = self.collect_workspace_files().await;
+        let mut progress = WorkspaceProgress::begin(self, files.len()).await;
+
+        let workers = std::thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(1)
+            .min(files.len().max(1));
+        let chunk_size = files.len().div_ceil(workers).max(1);
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        for chunk in files.chunks(chunk_size) {
+            let backend = self.clone();
+            let chunk = chunk.to_vec();
+            let done_tx = done_tx.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut parser = amble_parser();
+                for path in chunk {
+                    backend.index_file(&mut parser, &path);
+                    let _ = done_tx.send(());
+                }
+            });
+        }
+        drop(done_tx);
+
+        let mut indexed = 0;
+        while done_rx.recv().await.is_some() {
+            indexed += 1;
+            if let Some(progress) = progress.as_mut() {
+                progress.report(indexed).await;
+            }
+        }
+
+        if let Some(progress) = progress {
+            progress.end(indexed).await;
+        }
+        self.publish_pending_diagnostics().await;
+    }
+
+    /// Walks each workspace root on its own blocking task; nested roots are only counted once.
+    async fn collect_workspace_files(&self) -> Vec<PathBuf> {
+        let roots = self.workspace_roots.read().clone();
+        let walks: Vec<_> = roots
+            .into_iter()
+            .map(|root| tokio::task::spawn_blocking(move || amble_files_under(&root)))
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        for walk in walks {
+            let Ok(paths) = walk.await else {
+                continue;
+            };
+            for path in paths {
+                if seen.insert(path.clone()) {
+                    files.push(path);
+                }
+            }
+        }
+        files
+    }
+}
+
+/// A `window/workDoneProgress` session; only exists if the client accepted the token.
+struct WorkspaceProgress<'a> {
+    backend: &'a Backend,
+    total: usize,
+    last_percentage: u32,
+}
+
+impl<'a> WorkspaceProgress<'a> {
+    async fn begin(backend: &'a Backend, total: usize) -> Option<WorkspaceProgress<'a>> {
+        backend
+            .client
+            .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: progress_token(),
+            })
+            .await
+            .ok()?;
+
+        let progress = WorkspaceProgress {
+            backend,
+            total,
+            last_percentage: 0,
+        };
+        progress
+            .send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: "Indexing Amble files".to_string(),
+                cancellable: Some(false),
+                message: Some(indexing_message(0, total)),
+                percentage: Some(0),
+            }))
+            .await;
+        Some(progress)
+    }
+
+    /// Reports at most once per percentage point so large worlds don't flood the client.
+    async fn report(&mut self, indexed: usize) {
+        let percentage = (indexed * 100 / self.total.max(1)) as u32;
+        if percentage == self.last_percentage && indexed != self.total {
+            return;
+        }
+        self.last_percentage = percentage;
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(indexing_message(indexed, self.total)),
+            percentage: Some(percentage),
+        }))
+        .await;
+    }
+
+    async fn end(self, indexed: usize) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(format!("Indexed {} files", indexed)),
+        }))
+        .await;
+    }
+
+    async fn send(&self, value: WorkDoneProgress) {
+        self.backend
+            .client
+            .send_notification::<Progress>(ProgressParams {
+                token: progress_token(),
+                value: ProgressParamsValue::WorkDone(value),
+            })
+            .await;
+    }
+}
+
+fn progress_token() -> NumberOrString {
+    NumberOrString::String(INDEXING_PROGRESS_TOKEN.to_string())
+}
+
+fn indexing_message(indexed: usize, total: usize) -> String {
+    format!("Indexing {}/{} files", indexed, total)
+}
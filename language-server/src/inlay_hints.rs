@@ -0,0 +1,143 @@
+use crate::backend::Backend;
+use crate::symbols::{SymbolKind, SymbolMetadata, SymbolOccurrence};
+use tower_lsp::lsp_types::{InlayHint, InlayHintLabel, Range, Url};
+
+impl Backend {
+    /// Display names after room, item and NPC references and the limit after sequence flag
+    /// references within `range`, filtered by the client's inlay hint settings.
+    pub(crate) fn inlay_hints(&self, uri: &Url, range: Range) -> Vec<InlayHint> {
+        let Some(occurrences) = self.document_symbols.get(&uri.to_string()) else {
+            return Vec::new();
+        };
+
+        let mut hints: Vec<InlayHint> = Vec::new();
+        for occurrence in occurrences.iter() {
+            if occurrence.range.end < range.start || occurrence.range.end > range.end {
+                continue;
+            }
+            // Definitions already spell out their name or limit next to the id.
+            if self.is_definition_occurrence(uri, occurrence) {
+                continue;
+            }
+            let Some(label) = self.inlay_hint_label(occurrence) else {
+                continue;
+            };
+            let position = occurrence.range.end;
+            if hints.iter().any(|hint| hint.position == position) {
+                continue;
+            }
+
+            hints.push(InlayHint {
+                position,
+                label: InlayHintLabel::String(label),
+                kind: None,
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(true),
+                padding_right: None,
+                data: None,
+            });
+        }
+
+        hints.sort_by_key(|hint| (hint.position.line, hint.position.character));
+        hints
+    }
+
+    fn inlay_hint_label(&self, occurrence: &SymbolOccurrence) -> Option<String> {
+        let settings = self.settings.read().inlay_hints;
+        let enabled = match occurrence.kind {
+            SymbolKind::Room => settings.room_names,
+            SymbolKind::Item => settings.item_names,
+            SymbolKind::Npc => settings.npc_names,
+            SymbolKind::Flag => settings.flag_limits,
+            _ => false,
+        };
+        if !enabled {
+            return None;
+        }
+
+        let definition = self
+            .symbols
+            .index(occurrence.kind)
+            .definition(&occurrence.id)?;
+        match &definition.metadata {
+            SymbolMetadata::Room(meta) => meta.name.clone(),
+            SymbolMetadata::Item(meta) => meta.name.clone(),
+            SymbolMetadata::Npc(meta) => meta.name.clone(),
+            SymbolMetadata::Flag(meta) => meta.sequence_limit.map(|limit| format!("of {}", limit)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::InlayHintSettings;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::LspService;
+
+    const SOURCE: &str = r#"room hall {
+    name "Great Hall"
+    desc "A hall."
+    exit north -> porch
+}
+
+room porch {
+    name "Porch"
+    desc "A porch."
+    exit south -> hall
+}
+
+trigger "Count" when always {
+    do add seq flag counter limit 3
+    do advance flag counter
+    if has flag counter#2 {
+        do push player to porch
+    }
+}
+"#;
+
+    fn labels(backend: &Backend, uri: &Url) -> Vec<(u32, String)> {
+        let everything = Range {
+            start: Position::new(0, 0),
+            end: Position::new(u32::MAX, 0),
+        };
+        backend
+            .inlay_hints(uri, everything)
+            .into_iter()
+            .map(|hint| match hint.label {
+                InlayHintLabel::String(label) => (hint.position.line, label),
+                InlayHintLabel::LabelParts(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hints_names_and_limits_after_references_only() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = Url::parse("file:///world/rooms.amble").unwrap();
+        backend.analyze_document(&uri, SOURCE);
+
+        assert_eq!(
+            labels(backend, &uri),
+            [
+                (3, "Porch".to_string()),
+                (9, "Great Hall".to_string()),
+                (14, "of 3".to_string()),
+                (15, "of 3".to_string()),
+                (16, "Porch".to_string()),
+            ]
+        );
+
+        backend.settings.write().inlay_hints = InlayHintSettings {
+            room_names: false,
+            ..InlayHintSettings::default()
+        };
+        assert_eq!(
+            labels(backend, &uri),
+            [(14, "of 3".to_string()), (15, "of 3".to_string())]
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use crate::analysis::{FlagUseKind, FlagUser, RoomEdge};
+use crate::backend::Backend;
+use crate::symbols::{GoalConditionKind, SymbolKind, SymbolMetadata};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tower_lsp::lsp_types::Url;
+
+/// How long typing has to pause before diagnostics are re-published.
+const DIAGNOSTICS_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The parts of one document that diagnostics, semantic tokens and inlay hints in other
+/// documents can depend on.
+#[derive(Debug, Default)]
+pub(crate) struct DocumentFootprint {
+    /// Every id the document defines or references.
+    pub ids: HashSet<(SymbolKind, String)>,
+    /// The subset of `ids` the document defines, so turning a definition into a reference (or
+    /// deleting it while references remain) still counts as a change.
+    pub definitions: HashSet<(SymbolKind, String)>,
+    /// `sequence_limit` of each flag the document defines; references elsewhere are checked
+    /// against it.
+    pub flag_limits: HashMap<String, Option<i64>>,
+    pub player_starts: Vec<String>,
+    pub room_edges: Vec<RoomEdge>,
+    /// `(goal, when, kind, id)` for each `start`, `done` and `fail` condition of the goals the
+    /// document defines.
+    pub goal_conditions: Vec<(String, &'static str, GoalConditionKind, String)>,
+    /// Flags the document sets, with the trigger or action set that sets them.
+    pub flag_setters: Vec<(String, FlagUser)>,
+    /// Action sets the document runs; their flags count as set by a trigger.
+    pub action_set_runs: Vec<String>,
+    /// `name` of each room, item and NPC the document defines, shown as inlay hints.
+    pub display_names: HashMap<(SymbolKind, String), String>,
+}
+
+impl DocumentFootprint {
+    /// Ids whose definitions or references differ between `self` (before) and `current`.
+    pub(crate) fn changed_ids(&self, current: &DocumentFootprint) -> HashSet<(SymbolKind, String)> {
+        let mut changed: HashSet<(SymbolKind, String)> = self
+            .ids
+            .symmetric_difference(&current.ids)
+            .chain(self.definitions.symmetric_difference(&current.definitions))
+            .cloned()
+            .collect();
+        for (flag, limit) in &current.flag_limits {
+            if self.flag_limits.get(flag) != Some(limit) {
+                changed.insert((SymbolKind::Flag, flag.clone()));
+            }
+        }
+        for flag in self.flag_limits.keys() {
+            if !current.flag_limits.contains_key(flag) {
+                changed.insert((SymbolKind::Flag, flag.clone()));
+            }
+        }
+        changed
+    }
+
+    /// Whether goal diagnostics elsewhere, which follow goal prerequisites and the flags
+    /// triggers set, may differ between `self` (before) and `current`.
+    pub(crate) fn goal_inputs_changed(&self, current: &DocumentFootprint) -> bool {
+        self.goal_conditions != current.goal_conditions
+            || self.flag_setters != current.flag_setters
+            || self.action_set_runs != current.action_set_runs
+    }
+
+    /// Whether semantic tokens or inlay hints elsewhere, which mark undefined ids and show
+    /// display names and sequence limits, may differ between `self` (before) and `current`.
+    pub(crate) fn presentation_changed(&self, current: &DocumentFootprint) -> bool {
+        self.definitions != current.definitions
+            || self.display_names != current.display_names
+            || self.flag_limits != current.flag_limits
+    }
+}
+
+impl Backend {
+    /// Snapshots what `uri` currently contributes to the workspace; cost is proportional to
+    /// the document, not the workspace.
+    pub(crate) fn document_footprint(&self, uri: &Url) -> DocumentFootprint {
+        let uri_str = uri.to_string();
+        let mut footprint = DocumentFootprint::default();
+
+        if let Some(occurrences) = self.document_symbols.get(&uri_str) {
+            for occurrence in occurrences.iter() {
+                let key = (occurrence.kind, occurrence.id.clone());
+                if self.is_definition_occurrence(uri, occurrence) {
+                    footprint.definitions.insert(key.clone());
+                } else if occurrence.kind == SymbolKind::ActionSet {
+                    footprint.action_set_runs.push(occurrence.id.clone());
+                }
+                footprint.ids.insert(key);
+            }
+        }
+        footprint.action_set_runs.sort();
+        footprint.action_set_runs.dedup();
+
+        for (kind, id) in &footprint.ids {
+            if *kind != SymbolKind::Flag {
+                continue;
+            }
+            let Some(definition) = self.symbols.flags.definition(id) else {
+                continue;
+            };
+            if definition.location.uri != *uri {
+                continue;
+            }
+            if let SymbolMetadata::Flag(meta) = &definition.metadata {
+                footprint
+                    .flag_limits
+                    .insert(id.clone(), meta.sequence_limit);
+            }
+        }
+
+        for (kind, id) in &footprint.definitions {
+            let Some(definition) = self.symbols.index(*kind).definition(id) else {
+                continue;
+            };
+            let name = match &definition.metadata {
+                SymbolMetadata::Room(meta) => meta.name.as_ref(),
+                SymbolMetadata::Item(meta) => meta.name.as_ref(),
+                SymbolMetadata::Npc(meta) => meta.name.as_ref(),
+                _ => None,
+            };
+            if let Some(name) = name {
+                footprint
+                    .display_names
+                    .insert((*kind, id.clone()), name.clone());
+            }
+            if let SymbolMetadata::Goal(meta) = &definition.metadata {
+                let conditions = [
+                    ("start", &meta.start),
+                    ("done", &meta.done),
+                    ("fail", &meta.fail),
+                ];
+                for (when, condition) in conditions {
+                    if let Some(condition) = condition {
+                        footprint.goal_conditions.push((
+                            id.clone(),
+                            when,
+                            condition.kind,
+                            condition.id.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+        footprint
+            .goal_conditions
+            .sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+        if let Some(uses) = self.flag_uses.get(&uri_str) {
+            footprint.flag_setters = uses
+                .iter()
+                .filter(|flag_use| flag_use.kind == FlagUseKind::Set)
+                .map(|flag_use| (flag_use.flag.clone(), flag_use.user.clone()))
+                .collect();
+        }
+        footprint.flag_setters.sort();
+        footprint.flag_setters.dedup();
+
+        if let Some(starts) = self.player_starts.get(&uri_str) {
+            footprint.player_starts = starts.iter().map(|start| start.room_id.clone()).collect();
+        }
+        if let Some(edges) = self.room_edges.get(&uri_str) {
+            footprint.room_edges = edges.clone();
+        }
+
+        footprint
+    }
+
+    /// Queues `uri` and every document whose diagnostics depend on what changed in it.
+    pub(crate) fn invalidate_dependents(
+        &self,
+        uri: &Url,
+        previous: &DocumentFootprint,
+        current: &DocumentFootprint,
+    ) {
+        self.pending_diagnostics.insert(uri.to_string());
+        if previous.presentation_changed(current) {
+            self.presentation_stale.store(true, Ordering::SeqCst);
+        }
+
+        // "No player start" and reachability warnings can land in any document.
+        if previous.player_starts != current.player_starts {
+            for entry in self.documents.iter() {
+                self.pending_diagnostics.insert(entry.key().clone());
+            }
+            return;
+        }
+
+        if previous.room_edges != current.room_edges {
+            for entry in self.symbols.rooms.definitions_iter() {
+                self.pending_diagnostics
+                    .insert(entry.value().location.uri.to_string());
+            }
+        }
+
+        // Goal cycles and unset `done` flags are reported in the goals' own documents.
+        if previous.goal_inputs_changed(current) {
+            for entry in self.symbols.goals.definitions_iter() {
+                self.pending_diagnostics
+                    .insert(entry.value().location.uri.to_string());
+            }
+        }
+
+        for (kind, id) in previous.changed_ids(current) {
+            let index = self.symbols.index(kind);
+            if let Some(definition) = index.definition(&id) {
+                self.pending_diagnostics
+                    .insert(definition.location.uri.to_string());
+            }
+            if let Some(duplicates) = index.duplicates(&id) {
+                for duplicate in duplicates.iter() {
+                    self.pending_diagnostics
+                        .insert(duplicate.location.uri.to_string());
+                }
+            }
+            if let Some(references) = index.references(&id) {
+                for reference in references.iter() {
+                    self.pending_diagnostics
+                        .insert(reference.location.uri.to_string());
+                }
+            }
+        }
+    }
+
+    /// Publishes queued diagnostics once edits stop arriving for `DIAGNOSTICS_DEBOUNCE`.
+    pub(crate) fn schedule_diagnostics(&self) {
+        let generation = self.diagnostics_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let backend = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(DIAGNOSTICS_DEBOUNCE).await;
+            if backend.diagnostics_generation.load(Ordering::SeqCst) == generation {
+                backend.publish_pending_diagnostics().await;
+            }
+        });
+    }
+
+    pub(crate) async fn publish_pending_diagnostics(&self) {
+        let uris: Vec<String> = self
+            .pending_diagnostics
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        if !uris.is_empty() {
+            let graphs = self.workspace_graphs();
+            for uri_str in uris {
+                self.pending_diagnostics.remove(&uri_str);
+                if let Ok(uri) = Url::parse(&uri_str) {
+                    self.check_diagnostics(&uri, &graphs).await;
+                }
+            }
+        }
+
+        self.refresh_presentation().await;
+    }
+
+    /// Asks the client to re-request semantic tokens and inlay hints once definitions or
+    /// display names changed, for whichever of the two it can refresh.
+    async fn refresh_presentation(&self) {
+        if !self.presentation_stale.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let (semantic_tokens, inlay_hints) = self.refresh_support();
+        if semantic_tokens {
+            let _ = self.client.semantic_tokens_refresh().await;
+        }
+        if inlay_hints {
+            let _ = self.client.inlay_hint_refresh().await;
+        }
+    }
+
+    /// Whether the client accepts `workspace/semanticTokens/refresh` and
+    /// `workspace/inlayHint/refresh` requests, in that order.
+    pub(crate) fn refresh_support(&self) -> (bool, bool) {
+        let capabilities = self.client_capabilities.read();
+        let workspace = capabilities.workspace.as_ref();
+        (
+            workspace
+                .and_then(|workspace| workspace.semantic_tokens.as_ref())
+                .and_then(|tokens| tokens.refresh_support)
+                .unwrap_or(false),
+            workspace
+                .and_then(|workspace| workspace.inlay_hint.as_ref())
+                .and_then(|hints| hints.refresh_support)
+                .unwrap_or(false),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::{FileChangeType, FileEvent};
+    use tower_lsp::LspService;
+
+    fn footprint(
+        ids: &[(SymbolKind, &str)],
+        flag_limits: &[(&str, Option<i64>)],
+    ) -> DocumentFootprint {
+        DocumentFootprint {
+            ids: ids
+                .iter()
+                .map(|(kind, id)| (*kind, id.to_string()))
+                .collect(),
+            flag_limits: flag_limits
+                .iter()
+                .map(|(flag, limit)| (flag.to_string(), *limit))
+                .collect(),
+            ..DocumentFootprint::default()
+        }
+    }
+
+    #[test]
+    fn changed_ids_cover_added_removed_and_resized_flags() {
+        let before = footprint(
+            &[
+                (SymbolKind::Room, "hall"),
+                (SymbolKind::Item, "lamp"),
+                (SymbolKind::Flag, "lit"),
+            ],
+            &[("lit", None)],
+        );
+        let after = footprint(
+            &[
+                (SymbolKind::Room, "hall"),
+                (SymbolKind::Item, "lantern"),
+                (SymbolKind::Flag, "lit"),
+            ],
+            &[("lit", Some(3))],
+        );
+
+        let changed = before.changed_ids(&after);
+        let expected: HashSet<(SymbolKind, String)> = [
+            (SymbolKind::Item, "lamp".to_string()),
+            (SymbolKind::Item, "lantern".to_string()),
+            (SymbolKind::Flag, "lit".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(changed, expected);
+        assert!(after.changed_ids(&after).is_empty());
+    }
+
+    #[test]
+    fn deleting_a_definition_requeues_references_elsewhere() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let rooms = Url::parse("file:///world/rooms.amble").unwrap();
+        let triggers = Url::parse("file:///world/triggers.amble").unwrap();
+
+        backend.analyze_document(
+            &rooms,
+            "room hall {\n    exit south -> porch\n}\nroom porch {\n    name \"Porch\"\n}\n",
+        );
+        backend.analyze_document(
+            &triggers,
+            "trigger \"Arrive\" when always {\n    do spawn item lamp into room porch\n}\n",
+        );
+        backend.pending_diagnostics.clear();
+
+        // `porch` is still referenced by the exit, so only its role in rooms.amble changes.
+        backend.analyze_document(&rooms, "room hall {\n    exit south -> porch\n}\n");
+        assert!(backend.pending_diagnostics.contains(&triggers.to_string()));
+    }
+
+    #[test]
+    fn deleting_a_folder_queues_its_documents_for_clearing() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let cellar = Url::parse("file:///world/cellar/rooms.amble").unwrap();
+        let hall = Url::parse("file:///world/hall.amble").unwrap();
+        for uri in [&cellar, &hall] {
+            backend.analyze_document(uri, "room cellar {\n}\n");
+            backend.indexed_documents.insert(uri.to_string(), None);
+        }
+        backend.pending_diagnostics.clear();
+
+        backend.apply_file_event(&FileEvent::new(
+            Url::parse("file:///world/cellar").unwrap(),
+            FileChangeType::DELETED,
+        ));
+        assert!(!backend.documents.contains_key(&cellar.to_string()));
+        assert!(backend.documents.contains_key(&hall.to_string()));
+        // Published with no diagnostics, so the client drops the deleted file's problems.
+        assert!(backend.pending_diagnostics.contains(&cellar.to_string()));
+        let graphs = backend.workspace_graphs();
+        assert_eq!(backend.collect_diagnostics(&cellar, &graphs), None);
+
+        // Deletes under build output and VCS folders are dropped before any scan.
+        let output = Url::parse("file:///world/target/rooms.amble").unwrap();
+        backend.analyze_document(&output, "room cellar {\n}\n");
+        backend.indexed_documents.insert(output.to_string(), None);
+        backend.apply_file_event(&FileEvent::new(
+            Url::parse("file:///world/target").unwrap(),
+            FileChangeType::DELETED,
+        ));
+        assert!(backend.documents.contains_key(&output.to_string()));
+    }
+
+    #[test]
+    fn renaming_or_defining_marks_tokens_and_hints_stale() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let rooms = Url::parse("file:///world/rooms.amble").unwrap();
+        let room = |name: &str, desc: &str| {
+            format!(
+                "room hall {{\n    name \"{}\"\n    desc \"{}\"\n}}\n",
+                name, desc
+            )
+        };
+        backend.analyze_document(&rooms, &room("Hall", "Bare."));
+        let stale = || backend.presentation_stale.swap(false, Ordering::SeqCst);
+        assert!(stale());
+
+        backend.analyze_document(&rooms, &room("Hall", "Dusty."));
+        assert!(!stale());
+        backend.analyze_document(&rooms, &room("Great Hall", "Dusty."));
+        assert!(stale());
+        backend.analyze_document(&rooms, "room porch {\n}\n");
+        assert!(stale());
+    }
+}
@@ -0,0 +1,2126 @@
+use crate::analysis::range_from_node;
+use crate::backend::Backend;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Hover, HoverContents, InsertTextFormat,
+    MarkupContent, MarkupKind, Position, Range, TextEdit, Url,
+};
+use tree_sitter::Node;
+
+/// Where a statement form may be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FormScope {
+    Room,
+    Item,
+    Npc,
+    /// Statements directly inside a trigger, `if`, `schedule` or action set block.
+    Trigger,
+    /// What may follow `do`.
+    Action,
+    /// What may follow `if`, `visible when` or sit inside `all(...)`/`any(...)`.
+    Condition,
+    /// What may follow `overlay if`.
+    OverlayCondition,
+    // The scopes below are only documented for hover, not completed.
+    /// Top-level definitions.
+    Definition,
+    /// Modifiers between a trigger's name and `when`.
+    TriggerOption,
+    /// What may follow a trigger's `when`.
+    Event,
+    Consumable,
+    /// Options inside an exit's `{ }`.
+    ExitOption,
+    Goal,
+    /// What may follow a goal's `start when`, `done when` or `fail when`.
+    GoalCondition,
+    Spinner,
+}
+
+/// One statement, action or condition shape accepted by the grammar.
+///
+/// `syntax` is written the way it's typed: `<slot>` is an argument, `<slot:a|b>` an argument
+/// with a fixed set of values and `[...]` an optional part. `body` lines go inside a trailing
+/// `{ }` block. `kind` is the tree-sitter node the form parses to.
+#[derive(Debug)]
+pub(crate) struct Form {
+    pub scope: FormScope,
+    pub kind: &'static str,
+    pub syntax: &'static str,
+    pub body: &'static [&'static str],
+    pub summary: &'static str,
+    pub example: &'static str,
+}
+
+const fn form(
+    scope: FormScope,
+    kind: &'static str,
+    syntax: &'static str,
+    summary: &'static str,
+    example: &'static str,
+) -> Form {
+    Form {
+        scope,
+        kind,
+        syntax,
+        body: &[],
+        summary,
+        example,
+    }
+}
+
+const fn block(
+    scope: FormScope,
+    kind: &'static str,
+    syntax: &'static str,
+    body: &'static [&'static str],
+    summary: &'static str,
+    example: &'static str,
+) -> Form {
+    Form {
+        scope,
+        kind,
+        syntax,
+        body,
+        summary,
+        example,
+    }
+}
+
+use FormScope::*;
+
+pub(crate) const FORMS: &[Form] = &[
+    // Rooms
+    form(
+        Room,
+        "room_name",
+        "name \"<name>\"",
+        "Room name shown to the player",
+        "name \"Great Hall\"",
+    ),
+    form(
+        Room,
+        "room_desc",
+        "desc \"<description>\"",
+        "Room description",
+        "desc \"A draughty hall with a vaulted ceiling.\"",
+    ),
+    form(
+        Room,
+        "room_visited",
+        "visited <visited:true|false>",
+        "Whether the room starts out visited",
+        "visited true",
+    ),
+    form(
+        Room,
+        "room_exit",
+        "exit <direction> -> <room>",
+        "Exit leading to another room",
+        "exit north -> courtyard",
+    ),
+    block(
+        Room,
+        "room_exit",
+        "exit <direction> -> <room>",
+        &["<option:locked|hidden>"],
+        "Exit with options such as locked, hidden, barred or required flags/items",
+        "exit down -> cellar {\n    locked\n}",
+    ),
+    block(
+        Room,
+        "overlay_stmt",
+        "overlay if <condition>",
+        &["text \"<text>\""],
+        "Text appended to the description while the conditions hold",
+        "overlay if flag set lamp-lit, item present lamp {\n    \
+            text \"Lamplight flickers on the walls.\"\n}",
+    ),
+    block(
+        Room,
+        "ovl_flag_binary",
+        "overlay if flag <flag>",
+        &["set \"<text when set>\"", "unset \"<text when unset>\""],
+        "Alternative texts for a set and unset flag",
+        "overlay if flag lamp-lit {\n    set \"The hall is bright.\"\n    \
+            unset \"The hall is dark.\"\n}",
+    ),
+    block(
+        Room,
+        "ovl_presence_pair",
+        "overlay if item <item>",
+        &[
+            "present \"<text when present>\"",
+            "absent \"<text when absent>\"",
+        ],
+        "Alternative texts for an item being present or absent",
+        "overlay if item lamp {\n    \
+            present \"A lamp sits on the table.\"\n    \
+            absent \"The table is bare.\"\n}",
+    ),
+    block(
+        Room,
+        "ovl_presence_pair",
+        "overlay if npc <npc>",
+        &[
+            "present \"<text when present>\"",
+            "absent \"<text when absent>\"",
+        ],
+        "Alternative texts for an NPC being present or absent",
+        "overlay if npc guard {\n    present \"A guard blocks the door.\"\n    \
+            absent \"The door is unguarded.\"\n}",
+    ),
+    block(
+        Room,
+        "ovl_npc_state_set",
+        "overlay if npc <npc> here",
+        &["<state> \"<text>\""],
+        "Text per state of an NPC in the room",
+        "overlay if npc guard here {\n    \
+            normal \"The guard watches you.\"\n    \
+            custom(asleep) \"The guard snores.\"\n}",
+    ),
+    form(
+        Room,
+        "room_scenery_entry",
+        "scenery \"<name>\" [desc \"<description>\"]",
+        "Scenery the player can look at without an item",
+        "scenery \"tapestry\" desc \"A faded hunting scene.\"",
+    ),
+    form(
+        Room,
+        "room_scenery_default",
+        "scenery default \"<text>\"",
+        "Response for scenery without its own description",
+        "scenery default \"Nothing special.\"",
+    ),
+    // Items
+    form(
+        Item,
+        "item_name_stmt",
+        "name \"<name>\"",
+        "Item name shown to the player",
+        "name \"Brass Lamp\"",
+    ),
+    form(
+        Item,
+        "item_desc_stmt",
+        "desc \"<description>\"",
+        "Item description",
+        "desc \"A dented brass lamp.\"",
+    ),
+    form(
+        Item,
+        "item_text_stmt",
+        "text \"<text>\"",
+        "Text shown when the item is read",
+        "text \"Property of the lighthouse.\"",
+    ),
+    form(
+        Item,
+        "item_loc_stmt",
+        "location room <room>",
+        "Item starts in a room",
+        "location room hall",
+    ),
+    form(
+        Item,
+        "item_loc_stmt",
+        "location chest <container>",
+        "Item starts inside a container item",
+        "location chest toolbox",
+    ),
+    form(
+        Item,
+        "item_loc_stmt",
+        "location npc <npc>",
+        "Item starts carried by an NPC",
+        "location npc guard",
+    ),
+    form(
+        Item,
+        "item_loc_stmt",
+        "location inventory player",
+        "Item starts in the player's inventory",
+        "location inventory player",
+    ),
+    form(
+        Item,
+        "item_loc_stmt",
+        "location nowhere \"<note>\"",
+        "Item starts out of play until spawned",
+        "location nowhere \"spawned when the crate breaks\"",
+    ),
+    form(
+        Item,
+        "item_movability_stmt",
+        "movability free",
+        "Item can be picked up",
+        "movability free",
+    ),
+    form(
+        Item,
+        "item_movability_stmt",
+        "movability <movability:fixed|restricted> \"<reason>\"",
+        "Item can't be picked up, or only under conditions",
+        "movability fixed \"It's bolted to the floor.\"",
+    ),
+    form(
+        Item,
+        "item_visibility_stmt",
+        "visibility <visibility:listed|scenery|hidden>",
+        "How the item appears in room listings",
+        "visibility scenery",
+    ),
+    form(
+        Item,
+        "item_visible_when_stmt",
+        "visible when <condition>",
+        "Item is only visible while the condition holds",
+        "visible when has flag lamp-lit",
+    ),
+    form(
+        Item,
+        "item_aliases_stmt",
+        "aliases \"<alias>\"",
+        "Extra names the parser accepts for the item",
+        "aliases \"light\", \"lantern\"",
+    ),
+    form(
+        Item,
+        "item_container_stmt",
+        "container state <state:open|closed|locked|\
+            transparentOpen|transparentClosed|transparentLocked|none>",
+        "Makes the item a container; transparent ones show their contents while shut",
+        "container state transparentLocked",
+    ),
+    form(
+        Item,
+        "item_ability_stmt",
+        "ability <ability> [<target>]",
+        "Something the item can do",
+        "ability TurnOn",
+    ),
+    form(
+        Item,
+        "item_requires_stmt",
+        "requires <ability> to <interaction>",
+        "Ability another item needs to interact with this one",
+        "requires ignite to burn",
+    ),
+    block(
+        Item,
+        "item_consumable_stmt",
+        "consumable",
+        &["uses_left <uses>", "when_consumed <effect:despawn>"],
+        "Item is used up after a number of uses",
+        "consumable {\n    uses_left 1\n    consume_on ability Eat\n    \
+            when_consumed despawn\n}",
+    ),
+    // NPCs
+    form(
+        Npc,
+        "npc_name_stmt",
+        "name \"<name>\"",
+        "NPC name shown to the player",
+        "name \"Night Guard\"",
+    ),
+    form(
+        Npc,
+        "npc_desc_stmt",
+        "desc \"<description>\"",
+        "NPC description",
+        "desc \"A tired guard in a rumpled uniform.\"",
+    ),
+    form(
+        Npc,
+        "npc_max_hp_stmt",
+        "max_hp <hp>",
+        "NPC health",
+        "max_hp 20",
+    ),
+    form(
+        Npc,
+        "npc_loc_stmt",
+        "location room <room>",
+        "NPC starts in a room",
+        "location room gatehouse",
+    ),
+    form(
+        Npc,
+        "npc_loc_stmt",
+        "location nowhere \"<note>\"",
+        "NPC starts out of play until spawned",
+        "location nowhere \"arrives after the alarm\"",
+    ),
+    form(
+        Npc,
+        "npc_state_stmt",
+        "state <state>",
+        "Starting state of the NPC",
+        "state normal",
+    ),
+    form(
+        Npc,
+        "npc_movement_stmt",
+        "movement <movement:random|route> rooms (<room>)",
+        "Rooms the NPC wanders or patrols",
+        "movement route rooms (gatehouse, courtyard, hall)",
+    ),
+    block(
+        Npc,
+        "npc_dialogue_block",
+        "dialogue <state>",
+        &["\"<line>\""],
+        "Lines the NPC says in a state",
+        "dialogue normal {\n    \"Move along.\"\n    \"Nothing to see here.\"\n}",
+    ),
+    // Trigger blocks; every action is also offered here prefixed with `do`.
+    block(
+        Trigger,
+        "cond_block",
+        "if <condition>",
+        &["do <action>"],
+        "Actions that only run while the condition holds",
+        "if has item lamp {\n    do show \"The lamp flickers.\"\n}",
+    ),
+    form(
+        Trigger,
+        "run_stmt",
+        "run <action set>",
+        "Runs the actions of a named action set",
+        "run reset-puzzle",
+    ),
+    // Actions
+    form(
+        Action,
+        "action_show",
+        "show \"<text>\"",
+        "Shows a message to the player",
+        "show \"The floor creaks.\"",
+    ),
+    form(
+        Action,
+        "action_add_flag",
+        "add flag <flag>",
+        "Sets a flag",
+        "add flag lamp-lit",
+    ),
+    form(
+        Action,
+        "action_add_seq",
+        "add seq flag <flag> [limit <limit>]",
+        "Sets a sequence flag, optionally with a step limit",
+        "add seq flag fuse-burning limit 3",
+    ),
+    form(
+        Action,
+        "action_advance_flag",
+        "advance flag <flag>",
+        "Advances a sequence flag one step",
+        "advance flag fuse-burning",
+    ),
+    form(
+        Action,
+        "action_reset_flag",
+        "reset flag <flag>",
+        "Resets a sequence flag to its first step",
+        "reset flag fuse-burning",
+    ),
+    form(
+        Action,
+        "action_remove_flag",
+        "remove flag <flag>",
+        "Clears a flag",
+        "remove flag lamp-lit",
+    ),
+    form(
+        Action,
+        "action_add_wedge",
+        "add wedge \"<text>\" [width <width>] spinner <spinner>",
+        "Adds a weighted line to a spinner",
+        "add wedge \"A bell tolls.\" width 2 spinner ambience",
+    ),
+    form(
+        Action,
+        "action_spinner_msg",
+        "spinner message <spinner>",
+        "Shows a random line from a spinner",
+        "spinner message ambience",
+    ),
+    form(
+        Action,
+        "action_award_points",
+        "award points <points> reason \"<reason>\"",
+        "Changes the player's score",
+        "award points 5 reason \"lit the lamp\"",
+    ),
+    form(
+        Action,
+        "action_spawn_room",
+        "spawn item <item> into room <room>",
+        "Places an item in a room",
+        "spawn item lamp into room hall",
+    ),
+    form(
+        Action,
+        "action_spawn_container",
+        "spawn item <item> into container <container>",
+        "Places an item inside a container",
+        "spawn item fuse into container toolbox",
+    ),
+    form(
+        Action,
+        "action_spawn_inventory",
+        "spawn item <item> in inventory",
+        "Places an item in the player's inventory",
+        "spawn item ticket in inventory",
+    ),
+    form(
+        Action,
+        "action_spawn_current_room",
+        "spawn item <item> in current room",
+        "Places an item in the player's room",
+        "spawn item ashes in current room",
+    ),
+    form(
+        Action,
+        "action_spawn_npc_into_room",
+        "spawn npc <npc> into room <room>",
+        "Places an NPC in a room",
+        "spawn npc guard into room gatehouse",
+    ),
+    form(
+        Action,
+        "action_despawn_item",
+        "despawn item <item>",
+        "Removes an item from play",
+        "despawn item broken_lamp",
+    ),
+    form(
+        Action,
+        "action_despawn_npc",
+        "despawn npc <npc>",
+        "Removes an NPC from play",
+        "despawn npc guard",
+    ),
+    form(
+        Action,
+        "action_replace_item",
+        "replace item <item> with <replacement>",
+        "Swaps an item for another in the same place",
+        "replace item lamp with broken_lamp",
+    ),
+    form(
+        Action,
+        "action_replace_drop_item",
+        "replace drop item <item> with <replacement>",
+        "Swaps an item for another when it's dropped",
+        "replace drop item egg with broken_egg",
+    ),
+    form(
+        Action,
+        "action_lock_item",
+        "lock item <item>",
+        "Locks a container item",
+        "lock item toolbox",
+    ),
+    form(
+        Action,
+        "action_unlock_item",
+        "unlock item <item>",
+        "Unlocks a container item",
+        "unlock item toolbox",
+    ),
+    form(
+        Action,
+        "action_lock_exit",
+        "lock exit from <room> direction <direction>",
+        "Locks an exit",
+        "lock exit from hall direction north",
+    ),
+    form(
+        Action,
+        "action_unlock_exit",
+        "unlock exit from <room> direction <direction>",
+        "Unlocks an exit",
+        "unlock exit from hall direction north",
+    ),
+    form(
+        Action,
+        "action_reveal_exit",
+        "reveal exit from <room> to <room> direction <direction>",
+        "Reveals a hidden exit",
+        "reveal exit from hall to cellar direction down",
+    ),
+    form(
+        Action,
+        "action_set_barred_msg",
+        "set barred message from <room> to <room> \"<message>\"",
+        "Message shown when a barred exit is used",
+        "set barred message from hall to vault \"The vault door won't budge.\"",
+    ),
+    form(
+        Action,
+        "action_push_player",
+        "push player to <room>",
+        "Moves the player to a room",
+        "push player to courtyard",
+    ),
+    form(
+        Action,
+        "action_set_item_desc",
+        "set item description <item> \"<description>\"",
+        "Replaces an item's description",
+        "set item description lamp \"The lamp glows warmly.\"",
+    ),
+    form(
+        Action,
+        "action_set_item_movability",
+        "set item movability <item> free",
+        "Lets the player pick an item up",
+        "set item movability statue free",
+    ),
+    form(
+        Action,
+        "action_set_item_movability",
+        "set item movability <item> <movability:fixed|restricted> \"<reason>\"",
+        "Stops the player from picking an item up",
+        "set item movability statue fixed \"It's far too heavy.\"",
+    ),
+    form(
+        Action,
+        "action_set_container_state",
+        "set container state <item> <state:open|closed|locked|\
+            transparentOpen|transparentClosed|transparentLocked|none>",
+        "Changes a container's state",
+        "set container state toolbox open",
+    ),
+    form(
+        Action,
+        "action_give_to_player",
+        "give item <item> to player from npc <npc>",
+        "Moves an item from an NPC to the player",
+        "give item key to player from npc guard",
+    ),
+    form(
+        Action,
+        "action_npc_says",
+        "npc says <npc> \"<line>\"",
+        "Makes an NPC say a line",
+        "npc says guard \"Halt!\"",
+    ),
+    form(
+        Action,
+        "action_npc_random_dialogue",
+        "npc random dialogue <npc>",
+        "Makes an NPC say a line for its current state",
+        "npc random dialogue guard",
+    ),
+    form(
+        Action,
+        "action_npc_refuse_item",
+        "npc refuse item <npc> \"<reason>\"",
+        "Makes an NPC refuse an item it's given",
+        "npc refuse item guard \"I can't take bribes.\"",
+    ),
+    form(
+        Action,
+        "action_set_npc_state",
+        "set npc state <npc> <state>",
+        "Changes an NPC's state",
+        "set npc state guard happy",
+    ),
+    form(
+        Action,
+        "action_set_npc_active",
+        "set npc active <npc> <active:true|false>",
+        "Starts or stops an NPC's movement",
+        "set npc active guard false",
+    ),
+    form(
+        Action,
+        "action_damage_player",
+        "damage player <amount> [for <turns> turns] cause \"<cause>\"",
+        "Hurts the player, optionally over several turns",
+        "damage player 2 for 3 turns cause \"poison\"",
+    ),
+    form(
+        Action,
+        "action_heal_player",
+        "heal player <amount> [for <turns> turns] cause \"<cause>\"",
+        "Heals the player, optionally over several turns",
+        "heal player 5 cause \"bandage\"",
+    ),
+    form(
+        Action,
+        "action_damage_npc",
+        "damage npc <npc> <amount> [for <turns> turns] cause \"<cause>\"",
+        "Hurts an NPC, optionally over several turns",
+        "damage npc guard 3 cause \"thrown rock\"",
+    ),
+    form(
+        Action,
+        "action_heal_npc",
+        "heal npc <npc> <amount> [for <turns> turns] cause \"<cause>\"",
+        "Heals an NPC, optionally over several turns",
+        "heal npc guard 2 for 4 turns cause \"rest\"",
+    ),
+    form(
+        Action,
+        "action_remove_player_effect",
+        "remove player effect \"<cause>\"",
+        "Ends a lasting effect on the player",
+        "remove player effect \"poison\"",
+    ),
+    form(
+        Action,
+        "action_remove_npc_effect",
+        "remove npc <npc> effect \"<cause>\"",
+        "Ends a lasting effect on an NPC",
+        "remove npc guard effect \"rest\"",
+    ),
+    form(
+        Action,
+        "action_deny_read",
+        "deny read \"<message>\"",
+        "Stops the player reading an item",
+        "deny read \"It's too dark to read.\"",
+    ),
+    block(
+        Action,
+        "action_schedule",
+        "schedule in <turns> [note \"<note>\"]",
+        &["do <action>"],
+        "Runs actions a number of turns from now",
+        "schedule in 3 note \"fuse burns out\" {\n    do show \"The fuse fizzles out.\"\n}",
+    ),
+    block(
+        Action,
+        "action_schedule",
+        "schedule on <turn> [note \"<note>\"]",
+        &["do <action>"],
+        "Runs actions on a given turn",
+        "schedule on 20 {\n    do show \"A distant bell rings.\"\n}",
+    ),
+    block(
+        Action,
+        "action_modify_item",
+        "modify item <item>",
+        &["name \"<name>\""],
+        "Changes an item's definition",
+        "modify item lamp {\n    name \"Lit Lamp\"\n}",
+    ),
+    block(
+        Action,
+        "action_modify_room",
+        "modify room <room>",
+        &["name \"<name>\""],
+        "Changes a room's definition",
+        "modify room hall {\n    desc \"The hall lies in ruins.\"\n}",
+    ),
+    block(
+        Action,
+        "action_modify_npc",
+        "modify npc <npc>",
+        &["name \"<name>\""],
+        "Changes an NPC's definition",
+        "modify npc guard {\n    name \"Sleeping Guard\"\n}",
+    ),
+    // Conditions
+    form(
+        Condition,
+        "cond_has_flag",
+        "has flag <flag>",
+        "The flag is set",
+        "has flag lamp-lit",
+    ),
+    form(
+        Condition,
+        "cond_missing_flag",
+        "missing flag <flag>",
+        "The flag isn't set",
+        "missing flag lamp-lit",
+    ),
+    form(
+        Condition,
+        "cond_has_item",
+        "has item <item>",
+        "The player carries the item",
+        "has item lamp",
+    ),
+    form(
+        Condition,
+        "cond_missing_item",
+        "missing item <item>",
+        "The player doesn't carry the item",
+        "missing item lamp",
+    ),
+    form(
+        Condition,
+        "cond_visited_room",
+        "has visited room <room>",
+        "The player has been in the room",
+        "has visited room cellar",
+    ),
+    form(
+        Condition,
+        "cond_flag_in_progress",
+        "flag in progress <flag>",
+        "The sequence flag is set but not at its limit",
+        "flag in progress fuse-burning",
+    ),
+    form(
+        Condition,
+        "cond_flag_complete",
+        "flag complete <flag>",
+        "The sequence flag reached its limit",
+        "flag complete fuse-burning",
+    ),
+    form(
+        Condition,
+        "cond_with_npc",
+        "with npc <npc>",
+        "The NPC is in the player's room",
+        "with npc guard",
+    ),
+    form(
+        Condition,
+        "cond_npc_has_item",
+        "npc has item <npc> <item>",
+        "The NPC carries the item",
+        "npc has item guard key",
+    ),
+    form(
+        Condition,
+        "cond_npc_in_state",
+        "npc in state <npc> <state>",
+        "The NPC is in the state",
+        "npc in state guard happy",
+    ),
+    form(
+        Condition,
+        "cond_player_in_room",
+        "player in room <room>",
+        "The player is in the room",
+        "player in room hall",
+    ),
+    form(
+        Condition,
+        "cond_container_has_item",
+        "container <container> has item <item>",
+        "The container holds the item",
+        "container toolbox has item fuse",
+    ),
+    form(
+        Condition,
+        "cond_chance",
+        "chance <percent>%",
+        "Holds the given percentage of the time",
+        "chance 25%",
+    ),
+    form(
+        Condition,
+        "cond_ambient",
+        "ambient <spinner> [in rooms <room>]",
+        "Plays a spinner line, optionally only in some rooms",
+        "ambient birdsong in rooms courtyard, garden",
+    ),
+    form(
+        Condition,
+        "cond_in_rooms",
+        "in rooms <room>",
+        "The player is in one of the rooms",
+        "in rooms courtyard, garden",
+    ),
+    form(
+        Condition,
+        "cond_all_group",
+        "all(<condition>, <condition>)",
+        "Every condition holds",
+        "all(has item lamp, missing flag lamp-lit)",
+    ),
+    form(
+        Condition,
+        "cond_any_group",
+        "any(<condition>, <condition>)",
+        "At least one condition holds",
+        "any(has item lamp, has item torch)",
+    ),
+    // Overlay conditions
+    form(
+        OverlayCondition,
+        "ovl_flag_status",
+        "flag set <flag>",
+        "The flag is set",
+        "flag set lamp-lit",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_flag_status",
+        "flag unset <flag>",
+        "The flag isn't set",
+        "flag unset lamp-lit",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_flag_status",
+        "flag complete <flag>",
+        "The sequence flag reached its limit",
+        "flag complete fuse-burning",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_item_presence",
+        "item present <item>",
+        "The item is in the room",
+        "item present lamp",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_item_presence",
+        "item absent <item>",
+        "The item isn't in the room",
+        "item absent lamp",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_item_posession",
+        "player has item <item>",
+        "The player carries the item",
+        "player has item lamp",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_item_posession",
+        "player missing item <item>",
+        "The player doesn't carry the item",
+        "player missing item lamp",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_npc_presence",
+        "npc present <npc>",
+        "The NPC is in the room",
+        "npc present guard",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_npc_presence",
+        "npc absent <npc>",
+        "The NPC isn't in the room",
+        "npc absent guard",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_npc_state",
+        "npc in state <npc> <state>",
+        "The NPC is in the state",
+        "npc in state guard happy",
+    ),
+    form(
+        OverlayCondition,
+        "ovl_item_in_room",
+        "item in room <item> <room>",
+        "The item is in the given room",
+        "item in room lamp cellar",
+    ),
+    // Definitions
+    block(
+        Definition,
+        "room_def",
+        "room <room>",
+        &["name \"<name>\"", "desc \"<description>\""],
+        "A location the player can be in",
+        "room hall {\n    name \"Great Hall\"\n    \
+            desc \"A draughty hall.\"\n    exit north -> courtyard\n}",
+    ),
+    block(
+        Definition,
+        "item_def",
+        "item <item>",
+        &[
+            "name \"<name>\"",
+            "desc \"<description>\"",
+            "location room <room>",
+        ],
+        "An object in the world",
+        "item lamp {\n    name \"Brass Lamp\"\n    \
+            desc \"A dented brass lamp.\"\n    location room hall\n}",
+    ),
+    block(
+        Definition,
+        "npc_def",
+        "npc <npc>",
+        &[
+            "name \"<name>\"",
+            "desc \"<description>\"",
+            "location room <room>",
+        ],
+        "A character in the world",
+        "npc guard {\n    name \"Night Guard\"\n    \
+            desc \"A tired guard.\"\n    location room gatehouse\n}",
+    ),
+    block(
+        Definition,
+        "trigger_def",
+        "trigger \"<name>\" when <event>",
+        &["do <action>"],
+        "Actions that run when an event happens",
+        "trigger \"Light the lamp\" when use item lamp ability TurnOn {\n    \
+            do add flag lamp-lit\n}",
+    ),
+    block(
+        Definition,
+        "goal_def",
+        "goal <goal>",
+        &["name \"<name>\"", "done when <condition>"],
+        "An objective tracked for the player",
+        "goal light-the-way {\n    name \"Light the Way\"\n    \
+            group required\n    done when has flag lamp-lit\n}",
+    ),
+    block(
+        Definition,
+        "spinner_def",
+        "spinner <spinner>",
+        &["wedge \"<text>\""],
+        "A weighted set of random lines",
+        "spinner ambience {\n    wedge \"Wind howls.\" width 2\n    \
+            wedge \"A door slams.\"\n}",
+    ),
+    form(
+        Definition,
+        "set_decl",
+        "let set <set> = (<room>)",
+        "A named group of rooms",
+        "let set outdoors = (courtyard, garden)",
+    ),
+    form(
+        Definition,
+        "cond_decl",
+        "let cond <name> = <condition>",
+        "A named, reusable condition",
+        "let cond ready = all(has item lamp, has flag lamp-lit)",
+    ),
+    block(
+        Definition,
+        "action_set_decl",
+        "let actions <action set> =",
+        &["do <action>"],
+        "A named list of actions to `run` from triggers",
+        "let actions reset-puzzle = {\n    do remove flag lamp-lit\n}",
+    ),
+    // Trigger options
+    form(
+        TriggerOption,
+        "only_once_kw",
+        "only once",
+        "The trigger fires at most once per game",
+        "only once",
+    ),
+    form(
+        TriggerOption,
+        "trigger_note",
+        "note \"<note>\"",
+        "A note for authors; not shown to the player",
+        "note \"opens the cellar puzzle\"",
+    ),
+    // Events
+    form(
+        Event,
+        "always_event",
+        "always",
+        "Checked every turn",
+        "always",
+    ),
+    form(
+        Event,
+        "enter_room",
+        "enter room <room>",
+        "The player enters the room",
+        "enter room hall",
+    ),
+    form(
+        Event,
+        "leave_room",
+        "leave room <room>",
+        "The player leaves the room",
+        "leave room hall",
+    ),
+    form(
+        Event,
+        "look_at_item",
+        "look at item <item>",
+        "The player looks at the item",
+        "look at item tapestry",
+    ),
+    form(
+        Event,
+        "open_item",
+        "open item <item>",
+        "The player opens the item",
+        "open item toolbox",
+    ),
+    form(
+        Event,
+        "unlock_item",
+        "unlock item <item>",
+        "The player unlocks the item",
+        "unlock item toolbox",
+    ),
+    form(
+        Event,
+        "use_item",
+        "use item <item> ability <ability>",
+        "The player uses an ability of the item",
+        "use item lamp ability TurnOn",
+    ),
+    form(
+        Event,
+        "use_item_on_item",
+        "use item <item> on item <item> interaction <interaction>",
+        "The player uses one item on another",
+        "use item match on item fuse interaction burn",
+    ),
+    form(
+        Event,
+        "act_on_item",
+        "act <interaction> on item <item>",
+        "The player performs an interaction on the item",
+        "act burn on item fallen_tree",
+    ),
+    form(
+        Event,
+        "take_item",
+        "take <item>",
+        "The player takes the item",
+        "take lamp",
+    ),
+    form(
+        Event,
+        "take_from_npc",
+        "take <item> from npc <npc>",
+        "The player takes the item from the NPC",
+        "take key from npc guard",
+    ),
+    form(
+        Event,
+        "drop_item",
+        "drop item <item>",
+        "The player drops the item",
+        "drop item egg",
+    ),
+    form(
+        Event,
+        "insert_item_into",
+        "insert item <item> into item <container>",
+        "The player puts the item into a container",
+        "insert item fuse into item toolbox",
+    ),
+    form(
+        Event,
+        "give_to_npc",
+        "give item <item> to npc <npc>",
+        "The player gives the item to the NPC",
+        "give item ticket to npc guard",
+    ),
+    form(
+        Event,
+        "talk_to_npc",
+        "talk to npc <npc>",
+        "The player talks to the NPC",
+        "talk to npc guard",
+    ),
+    form(
+        Event,
+        "ingest_item",
+        "<ingest:eat|drink|inhale> item <item>",
+        "The player eats, drinks or inhales the item",
+        "eat item apple",
+    ),
+    // Consumables
+    form(
+        Consumable,
+        "consumable_uses",
+        "uses_left <uses>",
+        "How many uses the item has",
+        "uses_left 3",
+    ),
+    form(
+        Consumable,
+        "consumable_consume_on",
+        "consume_on ability <ability> [<target>]",
+        "Using this ability spends one use",
+        "consume_on ability Eat",
+    ),
+    form(
+        Consumable,
+        "consumable_when_consumed",
+        "when_consumed despawn",
+        "The item disappears once used up",
+        "when_consumed despawn",
+    ),
+    form(
+        Consumable,
+        "consumable_when_consumed",
+        "when_consumed replace inventory <item>",
+        "The item turns into another in the inventory once used up",
+        "when_consumed replace inventory empty_bottle",
+    ),
+    form(
+        Consumable,
+        "consumable_when_consumed",
+        "when_consumed replace current room <item>",
+        "The item turns into another in the room once used up",
+        "when_consumed replace current room ashes",
+    ),
+    // Exit options
+    form(
+        ExitOption,
+        "barred_stmt",
+        "barred \"<message>\"",
+        "Message shown while the exit's requirements aren't met",
+        "barred \"The gate is shut.\"",
+    ),
+    form(
+        ExitOption,
+        "required_flags_stmt",
+        "required_flags(<flag>)",
+        "Flags that must be set to use the exit",
+        "required_flags(gate-open)",
+    ),
+    form(
+        ExitOption,
+        "required_items_stmt",
+        "required_items(<item>)",
+        "Items the player must carry to use the exit",
+        "required_items(lamp)",
+    ),
+    // Goals
+    form(
+        Goal,
+        "goal_name_stmt",
+        "name \"<name>\"",
+        "Goal name shown to the player",
+        "name \"Light the Way\"",
+    ),
+    form(
+        Goal,
+        "goal_desc_stmt",
+        "desc \"<description>\"",
+        "Goal description",
+        "desc \"Find a way to light the cellar.\"",
+    ),
+    form(
+        Goal,
+        "goal_group_stmt",
+        "group <group:required|optional|status-effect>",
+        "Whether the goal counts towards finishing the game",
+        "group required",
+    ),
+    form(
+        Goal,
+        "goal_start_stmt",
+        "start when <condition>",
+        "When the goal becomes active",
+        "start when has item lamp",
+    ),
+    form(
+        Goal,
+        "goal_done_stmt",
+        "done when <condition>",
+        "When the goal is complete",
+        "done when has flag lamp-lit",
+    ),
+    form(
+        Goal,
+        "goal_fail_stmt",
+        "fail when <condition>",
+        "When the goal can no longer be completed",
+        "fail when missing flag lamp-lit",
+    ),
+    // Goal conditions
+    form(
+        GoalCondition,
+        "gc_has_flag",
+        "has flag <flag>",
+        "The flag is set",
+        "has flag lamp-lit",
+    ),
+    form(
+        GoalCondition,
+        "gc_missing_flag",
+        "missing flag <flag>",
+        "The flag isn't set",
+        "missing flag lamp-lit",
+    ),
+    form(
+        GoalCondition,
+        "gc_has_item",
+        "has item <item>",
+        "The player carries the item",
+        "has item lamp",
+    ),
+    form(
+        GoalCondition,
+        "gc_reached_room",
+        "reached room <room>",
+        "The player has reached the room",
+        "reached room cellar",
+    ),
+    form(
+        GoalCondition,
+        "gc_goal_complete",
+        "goal complete <goal>",
+        "Another goal is complete",
+        "goal complete find-the-lamp",
+    ),
+    form(
+        GoalCondition,
+        "gc_flag_progress",
+        "flag in progress <flag>",
+        "The sequence flag is set but not at its limit",
+        "flag in progress fuse-burning",
+    ),
+    form(
+        GoalCondition,
+        "gc_flag_complete",
+        "flag complete <flag>",
+        "The sequence flag reached its limit",
+        "flag complete fuse-burning",
+    ),
+    // Spinners
+    form(
+        Spinner,
+        "spinner_stmt",
+        "wedge \"<text>\" [width <width>]",
+        "A line the spinner can pick; wider wedges come up more often",
+        "wedge \"Wind howls.\" width 2",
+    ),
+];
+
+impl Form {
+    /// Leading literal words, which is what the user types before the first argument.
+    pub(crate) fn keywords(&self) -> Vec<&'static str> {
+        let end = self
+            .syntax
+            .find(['<', '"', '[', '('])
+            .unwrap_or(self.syntax.len());
+        self.syntax[..end].split_whitespace().collect()
+    }
+
+    /// The syntax with its block body, as shown in documentation.
+    fn display(&self) -> String {
+        let mut display = self.syntax.to_string();
+        if !self.body.is_empty() {
+            display.push_str(" {");
+            for line in self.body {
+                display.push_str("\n    ");
+                display.push_str(line);
+            }
+            display.push_str("\n}");
+        }
+        display
+    }
+
+    /// The syntax as an LSP snippet with a tab stop per argument.
+    fn snippet(&self) -> String {
+        let mut tab_stop = 0;
+        let mut snippet = render_snippet(self.syntax, &mut tab_stop);
+        if !self.body.is_empty() {
+            snippet.push_str(" {");
+            for line in self.body {
+                snippet.push_str("\n    ");
+                snippet.push_str(&render_snippet(line, &mut tab_stop));
+            }
+            snippet.push_str("\n}");
+        }
+        snippet
+    }
+}
+
+fn render_snippet(syntax: &str, tab_stop: &mut u32) -> String {
+    let mut snippet = String::new();
+    let mut rest = syntax;
+    while let Some(start) = rest.find(['<', '[']) {
+        snippet.push_str(&rest[..start]);
+        let close = if rest[start..].starts_with('<') {
+            '>'
+        } else {
+            ']'
+        };
+        let Some(len) = rest[start..].find(close) else {
+            break;
+        };
+        let inner = &rest[start + 1..start + len];
+        *tab_stop += 1;
+        let current = *tab_stop;
+        if close == ']' {
+            snippet.push_str(&format!(
+                "${{{}:{}}}",
+                current,
+                render_snippet(inner, tab_stop)
+            ));
+        } else if let Some((_, choices)) = inner.split_once(':') {
+            snippet.push_str(&format!("${{{}|{}|}}", current, choices.replace('|', ",")));
+        } else {
+            snippet.push_str(&format!("${{{}:{}}}", current, inner));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    snippet.push_str(rest);
+    snippet
+}
+
+/// Statement forms valid in `scope`; trigger blocks also get every action prefixed with `do`.
+fn forms_in_scope(scope: FormScope) -> impl Iterator<Item = (&'static str, &'static Form)> {
+    FORMS.iter().filter_map(move |form| {
+        if form.scope == scope {
+            Some(("", form))
+        } else if scope == Trigger && form.scope == Action {
+            Some(("do ", form))
+        } else {
+            None
+        }
+    })
+}
+
+/// Where a statement is being typed: the forms that fit, the byte offset the typed words
+/// start at, and the words themselves (the last one possibly partial).
+#[derive(Debug, PartialEq)]
+struct StatementContext<'a> {
+    scope: FormScope,
+    start: usize,
+    typed: Vec<&'a str>,
+    partial: bool,
+}
+
+/// The statement under the cursor: the forms that fit and the tokens typed after its anchor
+/// (`do`, `if`, a `(` or `,` in a condition list, or the start of the line).
+#[derive(Debug, PartialEq)]
+pub(crate) struct Statement<'a> {
+    pub scope: FormScope,
+    pub tokens: Vec<Token<'a>>,
+    /// The cursor is inside the last token, a word or an unterminated string.
+    pub partial: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Token<'a> {
+    /// A word and its byte offset.
+    Word(&'a str, usize),
+    Str,
+    Punct(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    Room,
+    Item,
+    Npc,
+    Trigger,
+    Other,
+}
+
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':' | '#')
+}
+
+/// Scans `text` up to `offset` to find the innermost block and the tokens typed so far on
+/// the current line. This is lexical on purpose: half-typed statements usually turn the whole
+/// trigger into an `ERROR` node, so the syntax tree can't tell us where we are.
+pub(crate) fn scan_statement(text: &str, offset: usize) -> Option<Statement<'_>> {
+    let text = text.get(..offset)?;
+    let bytes = text.as_bytes();
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut line: Vec<Token> = Vec::new();
+    let mut previous_head: Option<&str> = None;
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        match c {
+            '\n' => {
+                if let Some(Token::Word(word, _)) = line.first() {
+                    previous_head = Some(word);
+                }
+                line.clear();
+                i += 1;
+            }
+            _ if c.is_ascii_whitespace() => i += 1,
+            '#' => i = text[i..].find('\n').map_or(bytes.len(), |len| i + len),
+            '"' | '\'' => {
+                line.push(Token::Str);
+                // Unterminated strings mean the cursor is inside one.
+                let Some(end) = string_end(text, i) else {
+                    in_string = true;
+                    break;
+                };
+                i = end;
+            }
+            'r' if text[i + 1..].starts_with(['"', '#']) => {
+                let hashes = text[i + 1..].len() - text[i + 1..].trim_start_matches('#').len();
+                if !text[i + 1 + hashes..].starts_with('"') {
+                    let end = word_end(text, i);
+                    line.push(Token::Word(&text[i..end], i));
+                    i = end;
+                    continue;
+                }
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let body = i + 2 + hashes;
+                i = body + text[body..].find(&closing)? + closing.len();
+                line.push(Token::Str);
+            }
+            '{' => {
+                let enclosing = frames.last().copied();
+                frames.push(classify_block(&line, previous_head, enclosing));
+                line.clear();
+                previous_head = None;
+                i += 1;
+            }
+            '}' => {
+                frames.pop();
+                line.clear();
+                previous_head = None;
+                i += 1;
+            }
+            _ if is_word_char(c) => {
+                let end = word_end(text, i);
+                line.push(Token::Word(&text[i..end], i));
+                i = end;
+            }
+            _ => {
+                line.push(Token::Punct(c));
+                i += c.len_utf8().max(1);
+                while !text.is_char_boundary(i) {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    let partial = in_string || text.chars().next_back().is_some_and(is_word_char);
+    let word = |index: usize| match line.get(index) {
+        Some(Token::Word(word, _)) => Some(*word),
+        _ => None,
+    };
+
+    let anchor_after = |pred: &dyn Fn(&Token) -> bool| line.iter().rposition(pred).map(|i| i + 1);
+    let (base, nested) = match frames.last()? {
+        Frame::Room => (
+            Room,
+            (word(0) == Some("overlay") && word(1) == Some("if"))
+                .then(|| {
+                    anchor_after(&|token| {
+                        matches!(token, Token::Word("if", _) | Token::Punct('(' | ','))
+                    })
+                    .map(|anchor| (OverlayCondition, anchor))
+                })
+                .flatten(),
+        ),
+        Frame::Item => (
+            Item,
+            (word(0) == Some("visible") && word(1) == Some("when"))
+                .then(|| {
+                    anchor_after(&|token| {
+                        matches!(token, Token::Word("when", _) | Token::Punct('(' | ','))
+                    })
+                    .map(|anchor| (Condition, anchor))
+                })
+                .flatten(),
+        ),
+        Frame::Npc => (Npc, None),
+        Frame::Trigger => {
+            let condition = line
+                .iter()
+                .any(|token| matches!(token, Token::Word("if", _)))
+                .then(|| {
+                    anchor_after(&|token| {
+                        matches!(token, Token::Word("if", _) | Token::Punct('(' | ','))
+                    })
+                })
+                .flatten();
+            let nested = match condition {
+                Some(anchor) => Some((Condition, anchor)),
+                None if word(0) == Some("do") => {
+                    let anchor = if word(1) == Some("priority") { 3 } else { 1 };
+                    Some((Action, anchor.min(line.len())))
+                }
+                None => None,
+            };
+            (Trigger, nested)
+        }
+        Frame::Other => return None,
+    };
+    // A keyword still being typed (`do|`, `if|`) is completed as part of its statement.
+    let (scope, anchor) = match nested {
+        Some((_, anchor)) if partial && anchor >= line.len() => (base, 0),
+        Some(nested) => nested,
+        None => (base, 0),
+    };
+
+    line.drain(..anchor);
+    Some(Statement {
+        scope,
+        tokens: line,
+        partial,
+    })
+}
+
+/// The statement under the cursor if everything typed after its anchor is plain words.
+fn statement_context(text: &str, offset: usize) -> Option<StatementContext<'_>> {
+    let statement = scan_statement(text, offset)?;
+    let mut typed = Vec::new();
+    let mut start = offset;
+    for token in &statement.tokens {
+        let Token::Word(word, word_start) = token else {
+            return None;
+        };
+        if typed.is_empty() {
+            start = *word_start;
+        }
+        typed.push(*word);
+    }
+
+    Some(StatementContext {
+        scope: statement.scope,
+        start,
+        typed,
+        partial: statement.partial,
+    })
+}
+
+fn word_end(text: &str, start: usize) -> usize {
+    text[start..]
+        .find(|c: char| !is_word_char(c))
+        .map_or(text.len(), |len| start + len)
+}
+
+/// Byte offset just past the string starting at `start`, or `None` if it never closes.
+fn string_end(text: &str, start: usize) -> Option<usize> {
+    if text[start..].starts_with("\"\"\"") {
+        let body = start + 3;
+        return Some(body + text[body..].find("\"\"\"")? + 3);
+    }
+    let quote = text.as_bytes()[start];
+    let mut escaped = false;
+    for (index, byte) in text.as_bytes()[start + 1..].iter().enumerate() {
+        match byte {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            _ if *byte == quote => return Some(start + 1 + index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Works out what a `{` opens from the line it ends, falling back to the previous line for
+/// `trigger "..."` headers that put `when ... {` on their own line.
+fn classify_block(line: &[Token], previous_head: Option<&str>, enclosing: Option<Frame>) -> Frame {
+    let words: Vec<&str> = line
+        .iter()
+        .filter_map(|token| match token {
+            Token::Word(word, _) => Some(*word),
+            _ => None,
+        })
+        .collect();
+    let head = match words.first() {
+        Some(&"when" | &"only" | &"note") | None => previous_head,
+        Some(head) => Some(*head),
+    };
+    let in_trigger = enclosing == Some(Frame::Trigger);
+
+    match head {
+        Some("room") if enclosing.is_none() => Frame::Room,
+        Some("item") if enclosing.is_none() => Frame::Item,
+        Some("npc") if enclosing.is_none() => Frame::Npc,
+        Some("trigger") if enclosing.is_none() => Frame::Trigger,
+        Some("let") if words.get(1) == Some(&"actions") => Frame::Trigger,
+        Some("if" | "else") if in_trigger => Frame::Trigger,
+        Some("do") if in_trigger && words.contains(&"schedule") => Frame::Trigger,
+        _ => Frame::Other,
+    }
+}
+
+impl Backend {
+    /// Snippets for the statements, actions or conditions that fit where the cursor is.
+    pub(crate) fn keyword_completions(&self, uri: &Url, position: Position) -> Vec<CompletionItem> {
+        let Some(doc) = self.documents.get(&uri.to_string()) else {
+            return Vec::new();
+        };
+        let Some(offset) = doc.offset(position) else {
+            return Vec::new();
+        };
+        let Some(context) = statement_context(doc.text(), offset) else {
+            return Vec::new();
+        };
+        let range = Range {
+            start: doc.position_at(context.start),
+            end: position,
+        };
+
+        forms_in_scope(context.scope)
+            .enumerate()
+            .filter_map(|(index, (prefix, form))| {
+                let mut keywords = form.keywords();
+                if !prefix.is_empty() {
+                    keywords.insert(0, prefix.trim_end());
+                }
+                if !matches_typed(&keywords, &context.typed, context.partial) {
+                    return None;
+                }
+                Some(CompletionItem {
+                    label: format!("{}{}", prefix, form.syntax),
+                    kind: Some(CompletionItemKind::SNIPPET),
+                    detail: Some(form.summary.to_string()),
+                    filter_text: Some(keywords.join(" ")),
+                    sort_text: Some(format!("{:03}", index)),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                        range,
+                        new_text: format!("{}{}", prefix, form.snippet()),
+                    })),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Whether the words typed so far spell the start of `keywords`; a partial last word only
+/// needs to be a prefix.
+fn matches_typed(keywords: &[&str], typed: &[&str], partial: bool) -> bool {
+    if typed.len() > keywords.len() {
+        return false;
+    }
+    typed
+        .iter()
+        .zip(keywords)
+        .enumerate()
+        .all(|(index, (word, keyword))| {
+            if partial && index + 1 == typed.len() {
+                keyword.starts_with(word)
+            } else {
+                word == keyword
+            }
+        })
+}
+
+fn scope_label(scope: FormScope) -> &'static str {
+    match scope {
+        Room => "ROOM STATEMENT",
+        Item => "ITEM STATEMENT",
+        Npc => "NPC STATEMENT",
+        Trigger => "TRIGGER STATEMENT",
+        Action => "ACTION",
+        Condition => "CONDITION",
+        OverlayCondition => "OVERLAY CONDITION",
+        Definition => "DEFINITION",
+        TriggerOption => "TRIGGER OPTION",
+        Event => "TRIGGER EVENT",
+        Consumable => "CONSUMABLE",
+        ExitOption => "EXIT OPTION",
+        Goal => "GOAL STATEMENT",
+        GoalCondition => "GOAL CONDITION",
+        Spinner => "SPINNER STATEMENT",
+    }
+}
+
+/// The forms documenting `node`: those of its kind whose keywords best match its text.
+fn documented_forms(node: Node, text: &str) -> Vec<&'static Form> {
+    let candidates: Vec<&Form> = FORMS
+        .iter()
+        .filter(|form| form.kind == node.kind())
+        .collect();
+    let words: Vec<&str> = text[node.byte_range()]
+        .split(|c: char| !is_word_char(c))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let matching: Vec<(usize, &Form)> = candidates
+        .iter()
+        .map(|form| (form.keywords(), *form))
+        .filter(|(keywords, _)| words.starts_with(keywords))
+        .map(|(keywords, form)| (keywords.len(), form))
+        .collect();
+    let Some(best) = matching.iter().map(|(len, _)| *len).max() else {
+        return candidates;
+    };
+    matching
+        .into_iter()
+        .filter(|(len, _)| *len == best)
+        .map(|(_, form)| form)
+        .collect()
+}
+
+fn format_keyword_hover(forms: &[&Form]) -> String {
+    let first = forms[0];
+    let keywords = first.keywords();
+    let title = if keywords.is_empty() {
+        first.syntax.to_string()
+    } else {
+        keywords.join(" ")
+    };
+    let syntax: Vec<String> = forms.iter().map(|form| form.display()).collect();
+    let examples: Vec<&str> = forms.iter().map(|form| form.example).collect();
+    format!(
+        "**{}:** {}\n\n{}\n\n```amble\n{}\n```\n\n**Example:**\n```amble\n{}\n```",
+        scope_label(first.scope),
+        title,
+        first.summary,
+        syntax.join("\n"),
+        examples.join("\n")
+    )
+}
+
+impl Backend {
+    /// Reference documentation for the statement, action or condition whose keyword is at
+    /// `position`. Ids and strings are left to the symbol hover.
+    pub(crate) fn keyword_hover(&self, uri: &Url, position: Position) -> Option<Hover> {
+        let doc = self.documents.get(&uri.to_string())?;
+        let offset = doc.offset(position)?;
+        let text = doc.text();
+        let tree = doc.tree()?;
+        let leaf = tree.root_node().descendant_for_byte_range(offset, offset)?;
+        let is_keyword = !leaf.is_named()
+            && text[leaf.byte_range()].starts_with(|c: char| c.is_ascii_alphabetic());
+        if !is_keyword {
+            return None;
+        }
+
+        let mut node = leaf;
+        loop {
+            // `do` belongs to the action after it rather than the enclosing block.
+            if node.kind() == "do_action" {
+                let mut cursor = node.walk();
+                let action = node
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind().starts_with("action_"));
+                if let Some(action) = action {
+                    node = action;
+                }
+            }
+            let forms = documented_forms(node, text);
+            if !forms.is_empty() {
+                return Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: format_keyword_hover(&forms),
+                    }),
+                    range: Some(range_from_node(&doc, &node)),
+                });
+            }
+            node = node.parent()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::LspService;
+
+    /// Fills every argument with a value the grammar accepts there.
+    fn instantiate(syntax: &str, scope: FormScope) -> String {
+        let mut out = String::new();
+        let mut rest = syntax.replace(['[', ']'], "");
+        while let Some(start) = rest.find('<') {
+            out.push_str(&rest[..start]);
+            let end = start + rest[start..].find('>').unwrap();
+            let slot = &rest[start + 1..end];
+            let value = match slot.split_once(':') {
+                Some((_, choices)) => choices.split('|').next().unwrap().to_string(),
+                None if matches!(
+                    slot,
+                    "amount"
+                        | "turns"
+                        | "turn"
+                        | "points"
+                        | "limit"
+                        | "width"
+                        | "percent"
+                        | "hp"
+                        | "uses"
+                ) =>
+                {
+                    "2".to_string()
+                }
+                None if slot == "condition" && scope == Room => "flag set lit".to_string(),
+                None if slot == "condition" => "has flag lit".to_string(),
+                None if slot == "action" => "show \"x\"".to_string(),
+                None if slot == "event" => "always".to_string(),
+                None => "x1".to_string(),
+            };
+            out.push_str(&value);
+            rest = rest[end + 1..].to_string();
+        }
+        out.push_str(&rest);
+        out
+    }
+
+    fn source_for(form: &Form) -> String {
+        let mut statement = instantiate(form.syntax, form.scope);
+        if !form.body.is_empty() {
+            statement.push_str(" {\n");
+            for line in form.body {
+                statement.push_str(&instantiate(line, form.scope));
+                statement.push('\n');
+            }
+            statement.push('}');
+        }
+        in_scope(form.scope, &statement)
+    }
+
+    /// Wraps `statement` in the smallest document where `scope` applies.
+    fn in_scope(scope: FormScope, statement: &str) -> String {
+        match scope {
+            Room => format!("room hall {{\n{}\n}}\n", statement),
+            Item => format!("item lamp {{\n{}\n}}\n", statement),
+            Npc => format!("npc bob {{\n{}\n}}\n", statement),
+            Trigger => format!("trigger \"t\" when always {{\n{}\n}}\n", statement),
+            Action => format!("trigger \"t\" when always {{\ndo {}\n}}\n", statement),
+            Condition => format!(
+                "trigger \"t\" when always {{\nif {} {{\ndo show \"x\"\n}}\n}}\n",
+                statement
+            ),
+            OverlayCondition => format!(
+                "room hall {{\noverlay if {} {{\ntext \"x\"\n}}\n}}\n",
+                statement
+            ),
+            Definition => format!("{}\n", statement),
+            TriggerOption => format!(
+                "trigger \"t\" {} when always {{\ndo show \"x\"\n}}\n",
+                statement
+            ),
+            Event => format!("trigger \"t\" when {} {{\ndo show \"x\"\n}}\n", statement),
+            Consumable => format!("item lamp {{\nconsumable {{\n{}\n}}\n}}\n", statement),
+            ExitOption => format!(
+                "room hall {{\nexit north -> porch {{\n{}\n}}\n}}\n",
+                statement
+            ),
+            Goal => format!("goal g {{\nname \"G\"\n{}\n}}\n", statement),
+            GoalCondition => format!("goal g {{\nname \"G\"\ndone when {}\n}}\n", statement),
+            Spinner => format!("spinner s {{\n{}\n}}\n", statement),
+        }
+    }
+
+    #[test]
+    fn every_form_parses() {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&tree_sitter_amble::language()).unwrap();
+        let unparsed: Vec<String> = FORMS
+            .iter()
+            .map(source_for)
+            .filter(|source| parser.parse(source, None).unwrap().root_node().has_error())
+            .collect();
+        assert!(
+            unparsed.is_empty(),
+            "forms that don't parse:\n{:#?}",
+            unparsed
+        );
+    }
+
+    #[test]
+    fn every_example_parses_to_its_node_kind() {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&tree_sitter_amble::language()).unwrap();
+        let mismatched: Vec<String> = FORMS
+            .iter()
+            .filter(|form| {
+                let source = in_scope(form.scope, form.example);
+                let tree = parser.parse(&source, None).unwrap();
+                let root = tree.root_node();
+                let mut stack = vec![root];
+                let mut found = false;
+                while let Some(node) = stack.pop() {
+                    found |= node.kind() == form.kind;
+                    let mut cursor = node.walk();
+                    stack.extend(node.children(&mut cursor));
+                }
+                root.has_error() || !found
+            })
+            .map(|form| format!("{} => {}", form.kind, form.example))
+            .collect();
+        assert!(
+            mismatched.is_empty(),
+            "examples that don't parse to their kind:\n{:#?}",
+            mismatched
+        );
+    }
+
+    #[test]
+    fn renders_arguments_choices_and_optional_parts_as_tab_stops() {
+        let damage = FORMS
+            .iter()
+            .find(|form| form.syntax.starts_with("damage npc"))
+            .unwrap();
+        assert_eq!(damage.keywords(), ["damage", "npc"]);
+        assert_eq!(
+            damage.snippet(),
+            "damage npc ${1:npc} ${2:amount} ${3:for ${4:turns} turns} cause \"${5:cause}\""
+        );
+
+        let overlay = FORMS
+            .iter()
+            .find(|form| form.syntax == "overlay if flag <flag>")
+            .unwrap();
+        assert_eq!(
+            overlay.snippet(),
+            "overlay if flag ${1:flag} {\n    set \"${2:text when set}\"\n    unset \"${3:text when unset}\"\n}"
+        );
+
+        let active = FORMS
+            .iter()
+            .find(|form| form.syntax.starts_with("set npc active"))
+            .unwrap();
+        assert_eq!(active.snippet(), "set npc active ${1:npc} ${2|true,false|}");
+    }
+
+    fn context_at(source: &str) -> Option<StatementContext<'_>> {
+        statement_context(source, source.find('|').unwrap())
+    }
+
+    #[test]
+    fn finds_the_statement_being_typed() {
+        let room = "room hall {\n    name \"Hall\"\n    ov|\n}\n";
+        let context = context_at(room).unwrap();
+        assert_eq!((context.scope, context.typed), (Room, vec!["ov"]));
+        assert_eq!(context.start, room.find("ov").unwrap());
+
+        let action = "trigger \"t\"\nwhen always {\n    do spawn it|\n}\n";
+        let context = context_at(action).unwrap();
+        assert_eq!(
+            (context.scope, context.typed),
+            (Action, vec!["spawn", "it"])
+        );
+        assert!(context.partial);
+
+        let condition = "trigger \"t\" when always {\n    if all(has flag a, |\n}\n";
+        let context = context_at(condition).unwrap();
+        assert_eq!((context.scope, context.typed), (Condition, vec![]));
+
+        let typing_do = "trigger \"t\" when always {\n    do|\n}\n";
+        assert_eq!(context_at(typing_do).unwrap().scope, Trigger);
+
+        let schedule =
+            "trigger \"t\" when always {\n    do schedule in 2 {\n        do |\n    }\n}\n";
+        assert_eq!(context_at(schedule).unwrap().scope, Action);
+
+        let overlay = "room hall {\n    overlay if flag set a, np|\n}\n";
+        assert_eq!(context_at(overlay).unwrap().scope, OverlayCondition);
+
+        let visible = "item lamp {\n    visible when |\n}\n";
+        assert_eq!(context_at(visible).unwrap().scope, Condition);
+
+        let consumable = "item lamp {\n    consumable {\n        |\n    }\n}\n";
+        assert_eq!(context_at(consumable), None);
+
+        let in_string = "room hall {\n    name \"Ha|\n}\n";
+        assert_eq!(context_at(in_string), None);
+
+        let after_comment = "# room {\nitem lamp { # }\n    con|\n}\n";
+        assert_eq!(context_at(after_comment).unwrap().scope, Item);
+    }
+
+    /// The hover title and range text for the keyword at `|`.
+    fn hover_at(backend: &Backend, source: &str) -> Option<(String, String)> {
+        let uri = Url::parse("file:///world/world.amble").unwrap();
+        let offset = source.find('|').unwrap();
+        let text = source.replace('|', "");
+        backend.analyze_document(&uri, &text);
+        let line = text[..offset].matches('\n').count() as u32;
+        let character = (offset - text[..offset].rfind('\n').map_or(0, |i| i + 1)) as u32;
+
+        let hover = backend.keyword_hover(&uri, Position::new(line, character))?;
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("expected markdown");
+        };
+        // Only the first line of the hovered range, which is enough to tell nodes apart.
+        let range = hover.range.unwrap();
+        let first_line = text.lines().nth(range.start.line as usize).unwrap();
+        let end = if range.end.line == range.start.line {
+            range.end.character as usize
+        } else {
+            first_line.len()
+        };
+        let hovered = first_line[range.start.character as usize..end].to_string();
+        Some((markup.value.lines().next().unwrap().to_string(), hovered))
+    }
+
+    #[test]
+    fn documents_keywords_on_hover() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+
+        let trigger = "trigger \"Burn\" on|ly once when act burn on item tree {\n    \
+            d|o despawn item tree\n}\n";
+        let (title, hovered) = hover_at(backend, &trigger.replacen('|', "", 1)).unwrap();
+        assert_eq!(title, "**ACTION:** despawn item");
+        assert_eq!(hovered, "despawn item tree");
+        let (title, _) = hover_at(backend, &trigger.replacen("d|o", "do", 1)).unwrap();
+        assert_eq!(title, "**TRIGGER OPTION:** only once");
+
+        let item = "item apple {\n    \
+            container state transparent|Locked\n    \
+            consumable {\n        \
+            consume_o|n ability Eat\n    }\n}\n";
+        let (title, hovered) = hover_at(backend, &item.replacen("_o|n", "_on", 1)).unwrap();
+        assert_eq!(title, "**ITEM STATEMENT:** container state");
+        assert_eq!(hovered, "container state transparentLocked");
+        let (title, _) = hover_at(
+            backend,
+            &item.replacen("transparent|Locked", "transparentLocked", 1),
+        )
+        .unwrap();
+        assert_eq!(title, "**CONSUMABLE:** consume_on ability");
+
+        let room = "room hall {\n    \
+            overlay if npc guard he|re {\n        normal \"Watching.\"\n    }\n    \
+            overlay if flag un|set lamp {\n        text \"Dark.\"\n    }\n}\n";
+        let (title, _) = hover_at(backend, &room.replacen("un|set", "unset", 1)).unwrap();
+        assert_eq!(title, "**ROOM STATEMENT:** overlay if npc");
+        let (title, hovered) = hover_at(backend, &room.replacen("he|re", "here", 1)).unwrap();
+        assert_eq!(title, "**OVERLAY CONDITION:** flag unset");
+        assert_eq!(hovered, "flag unset lamp");
+
+        assert_eq!(
+            hover_at(backend, "room hall {\n    exit north -> cour|tyard\n}\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn completes_statements_as_snippets() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = Url::parse("file:///world/triggers.amble").unwrap();
+        let source = "trigger \"t\" when always {\n    do set ba\n}\n";
+        backend.analyze_document(&uri, source);
+
+        let items = backend.keyword_completions(&uri, Position::new(1, 13));
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            ["set barred message from <room> to <room> \"<message>\""]
+        );
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.range.start, Position::new(1, 7));
+        assert_eq!(
+            edit.new_text,
+            "set barred message from ${1:room} to ${2:room} \"${3:message}\""
+        );
+
+        let statement_start = backend.keyword_completions(&uri, Position::new(1, 4));
+        assert!(statement_start
+            .iter()
+            .any(|item| item.label == "do show \"<text>\""));
+        assert!(statement_start
+            .iter()
+            .any(|item| item.label == "if <condition>"));
+        assert!(statement_start
+            .iter()
+            .all(|item| item.label != "has flag <flag>"));
+    }
+}
@@ -72,6 +72,74 @@ const ACTION_SET_REF_QUERY: &str = r#"
   action_set_name: (action_set_name) @action_set.reference)
 "#;
 
+const SPINNER_DEF_QUERY: &str = r#"
+(spinner_def
+  name: (spinner_id) @spinner.definition)
+"#;
+
+const SPINNER_REF_QUERY: &str = r#"
+[
+  (action_spinner_msg
+    spinner: (spinner_id) @spinner.reference)
+  (action_add_wedge
+    spinner: (spinner_id) @spinner.reference)
+  (cond_ambient
+    spinner: (spinner_id) @spinner.reference)
+]
+"#;
+
+const GOAL_DEF_QUERY: &str = r#"
+(goal_def
+  goal_id: (goal_id) @goal.definition)
+"#;
+
+const GOAL_REF_QUERY: &str = r#"
+(gc_goal_complete
+  goal_id: (goal_id) @goal.reference)
+"#;
+
+const TRIGGER_DEF_QUERY: &str = r#"
+(trigger_def
+  name: (entity_name) @trigger.definition)
+"#;
+
+const NPC_STATE_DEF_QUERY: &str = r#"
+(npc_dialogue_block
+  (npc_state) @npc_state.definition)
+"#;
+
+const NPC_STATE_REF_QUERY: &str = r#"
+[
+  (npc_state_stmt
+    (npc_state) @npc_state.reference)
+  (action_set_npc_state
+    state: (_) @npc_state.reference)
+  (cond_npc_in_state
+    state: (_) @npc_state.reference)
+  (ovl_npc_state
+    npc_state: (_) @npc_state.reference)
+  (npc_state_set_line
+    [(identifier) (npc_state_set_custom)] @npc_state.reference)
+  (npc_patch_state
+    state: (_) @npc_state.reference)
+]
+"#;
+
+const ITEM_ABILITY_DEF_QUERY: &str = r#"
+[
+  (item_ability_stmt
+    ability: (item_ability) @item_ability.definition)
+  (item_patch_add_ability
+    ability: (patch_ability
+      ability_name: (ability_name) @item_ability.definition))
+]
+"#;
+
+const ITEM_ABILITY_REF_QUERY: &str = r#"
+(use_item
+  ability: (item_ability) @item_ability.reference)
+"#;
+
 pub struct Queries {
     pub room_definitions: Query,
     pub room_references: Query,
@@ -87,6 +155,15 @@ pub struct Queries {
     pub cond_references: Query,
     pub action_set_definitions: Query,
     pub action_set_references: Query,
+    pub spinner_definitions: Query,
+    pub spinner_references: Query,
+    pub goal_definitions: Query,
+    pub goal_references: Query,
+    pub trigger_definitions: Query,
+    pub npc_state_definitions: Query,
+    pub npc_state_references: Query,
+    pub item_ability_definitions: Query,
+    pub item_ability_references: Query,
 }
 
 impl Queries {
@@ -119,6 +196,24 @@ impl Queries {
                 .expect("Bad action set definition query"),
             action_set_references: Query::new(&language, ACTION_SET_REF_QUERY)
                 .expect("Bad action set reference query"),
+            spinner_definitions: Query::new(&language, SPINNER_DEF_QUERY)
+                .expect("Bad spinner definition query"),
+            spinner_references: Query::new(&language, SPINNER_REF_QUERY)
+                .expect("Bad spinner reference query"),
+            goal_definitions: Query::new(&language, GOAL_DEF_QUERY)
+                .expect("Bad goal definition query"),
+            goal_references: Query::new(&language, GOAL_REF_QUERY)
+                .expect("Bad goal reference query"),
+            trigger_definitions: Query::new(&language, TRIGGER_DEF_QUERY)
+                .expect("Bad trigger definition query"),
+            npc_state_definitions: Query::new(&language, NPC_STATE_DEF_QUERY)
+                .expect("Bad npc state definition query"),
+            npc_state_references: Query::new(&language, NPC_STATE_REF_QUERY)
+                .expect("Bad npc state reference query"),
+            item_ability_definitions: Query::new(&language, ITEM_ABILITY_DEF_QUERY)
+                .expect("Bad item ability definition query"),
+            item_ability_references: Query::new(&language, ITEM_ABILITY_REF_QUERY)
+                .expect("Bad item ability reference query"),
         }
     }
 }
@@ -0,0 +1,395 @@
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionOrCommand, Diagnostic, DiagnosticSeverity, NumberOrString, Range, Url,
+};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_ROOT_ID: &str = "WORLDROOT";
+
+/// One diagnostic from `amble-lsp check`, with the quick fixes the server would offer for it.
+pub(crate) struct Finding {
+    pub uri: Url,
+    pub diagnostic: Diagnostic,
+    pub fixes: Vec<CodeAction>,
+}
+
+impl Finding {
+    pub(crate) fn new(uri: Url, diagnostic: Diagnostic, actions: Vec<CodeActionOrCommand>) -> Self {
+        let fixes = actions
+            .into_iter()
+            .filter_map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => Some(action),
+                CodeActionOrCommand::Command(_) => None,
+            })
+            .collect();
+        Self {
+            uri,
+            diagnostic,
+            fixes,
+        }
+    }
+}
+
+/// Maps document URIs back to paths the user recognizes.
+pub(crate) struct WorldPaths {
+    /// The directory as typed on the command line.
+    pub world_dir: String,
+    /// The same directory, canonicalized.
+    pub root: PathBuf,
+}
+
+impl WorldPaths {
+    /// `world/rooms.amble` style path, relative to the current directory like the argument was.
+    pub(crate) fn display(&self, uri: &Url) -> String {
+        Path::new(&self.world_dir)
+            .join(self.relative(uri))
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    /// Path relative to the world root, e.g. `rooms.amble`.
+    pub(crate) fn relative(&self, uri: &Url) -> String {
+        let Ok(path) = uri.to_file_path() else {
+            return uri.to_string();
+        };
+        path.strip_prefix(&self.root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+pub(crate) fn severity_label(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        _ => "hint",
+    }
+}
+
+/// `file:line:col: severity: message`, one-based like compiler output.
+pub(crate) fn text_line(paths: &WorldPaths, finding: &Finding) -> String {
+    let diagnostic = &finding.diagnostic;
+    format!(
+        "{}:{}:{}: {}: {}",
+        paths.display(&finding.uri),
+        diagnostic.range.start.line + 1,
+        diagnostic.range.start.character + 1,
+        severity_label(diagnostic.severity),
+        diagnostic.message
+    )
+}
+
+/// A JSON array with one object per finding. Ranges keep LSP's zero-based lines and UTF-16
+/// columns so they can be fed straight back to editor tooling.
+pub(crate) fn json_report(paths: &WorldPaths, findings: &[Finding]) -> Value {
+    Value::Array(
+        findings
+            .iter()
+            .map(|finding| {
+                let diagnostic = &finding.diagnostic;
+                let related: Vec<Value> = diagnostic
+                    .related_information
+                    .iter()
+                    .flatten()
+                    .map(|related| {
+                        json!({
+                            "file": paths.display(&related.location.uri),
+                            "range": related.location.range,
+                            "message": related.message,
+                        })
+                    })
+                    .collect();
+                let fixes: Vec<Value> = finding
+                    .fixes
+                    .iter()
+                    .map(|fix| {
+                        let edits: Vec<Value> = fix_edits(fix)
+                            .map(|(uri, range, new_text)| {
+                                json!({
+                                    "file": paths.display(uri),
+                                    "range": range,
+                                    "newText": new_text,
+                                })
+                            })
+                            .collect();
+                        json!({ "title": fix.title, "edits": edits })
+                    })
+                    .collect();
+
+                json!({
+                    "file": paths.display(&finding.uri),
+                    "rule": rule_id(diagnostic),
+                    "severity": severity_label(diagnostic.severity),
+                    "message": diagnostic.message,
+                    "range": diagnostic.range,
+                    "related": related,
+                    "fixes": fixes,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// A SARIF 2.1.0 log with a single run. Artifact paths are relative to the world directory,
+/// which is recorded under the `WORLDROOT` base id.
+pub(crate) fn sarif_report(paths: &WorldPaths, findings: &[Finding]) -> Value {
+    let mut rule_ids: Vec<String> = findings
+        .iter()
+        .filter_map(|finding| rule_id(&finding.diagnostic))
+        .collect();
+    rule_ids.sort();
+    rule_ids.dedup();
+    let rules: Vec<Value> = rule_ids.iter().map(|id| json!({ "id": id })).collect();
+
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let diagnostic = &finding.diagnostic;
+            let related: Vec<Value> = diagnostic
+                .related_information
+                .iter()
+                .flatten()
+                .enumerate()
+                .map(|(index, related)| {
+                    json!({
+                        "id": index,
+                        "physicalLocation": sarif_location(
+                            paths,
+                            &related.location.uri,
+                            &related.location.range,
+                        ),
+                        "message": { "text": related.message },
+                    })
+                })
+                .collect();
+            let fixes: Vec<Value> = finding
+                .fixes
+                .iter()
+                .map(|fix| {
+                    let changes: Vec<Value> = fix_edits(fix)
+                        .map(|(uri, range, new_text)| {
+                            json!({
+                                "artifactLocation": sarif_artifact(paths, uri),
+                                "replacements": [{
+                                    "deletedRegion": sarif_region(range),
+                                    "insertedContent": { "text": new_text },
+                                }],
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "description": { "text": fix.title },
+                        "artifactChanges": changes,
+                    })
+                })
+                .collect();
+
+            let mut result = json!({
+                "level": sarif_level(diagnostic.severity),
+                "message": { "text": diagnostic.message },
+                "locations": [{
+                    "physicalLocation": sarif_location(paths, &finding.uri, &diagnostic.range),
+                }],
+            });
+            if let Some(rule) = rule_id(diagnostic) {
+                result["ruleId"] = json!(rule);
+            }
+            if !related.is_empty() {
+                result["relatedLocations"] = json!(related);
+            }
+            if !fixes.is_empty() {
+                result["fixes"] = json!(fixes);
+            }
+            result
+        })
+        .collect();
+
+    let root_uri = Url::from_directory_path(&paths.root)
+        .map(|uri| uri.to_string())
+        .unwrap_or_default();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "amble-lsp",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "originalUriBaseIds": {
+                SARIF_ROOT_ID: { "uri": root_uri },
+            },
+            "columnKind": "utf16CodeUnits",
+            "results": results,
+        }],
+    })
+}
+
+fn rule_id(diagnostic: &Diagnostic) -> Option<String> {
+    match diagnostic.code.as_ref()? {
+        NumberOrString::String(code) => Some(code.clone()),
+        NumberOrString::Number(code) => Some(code.to_string()),
+    }
+}
+
+fn fix_edits(fix: &CodeAction) -> impl Iterator<Item = (&Url, &Range, &str)> {
+    fix.edit
+        .iter()
+        .flat_map(|edit| edit.changes.iter().flatten())
+        .flat_map(|(uri, edits)| {
+            edits
+                .iter()
+                .map(move |edit| (uri, &edit.range, edit.new_text.as_str()))
+        })
+}
+
+fn sarif_level(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        _ => "note",
+    }
+}
+
+fn sarif_artifact(paths: &WorldPaths, uri: &Url) -> Value {
+    json!({ "uri": paths.relative(uri), "uriBaseId": SARIF_ROOT_ID })
+}
+
+fn sarif_location(paths: &WorldPaths, uri: &Url, range: &Range) -> Value {
+    json!({
+        "artifactLocation": sarif_artifact(paths, uri),
+        "region": sarif_region(range),
+    })
+}
+
+/// SARIF regions are one-based; columns are UTF-16 units to match LSP.
+fn sarif_region(range: &Range) -> Value {
+    json!({
+        "startLine": range.start.line + 1,
+        "startColumn": range.start.character + 1,
+        "endLine": range.end.line + 1,
+        "endColumn": range.end.character + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tower_lsp::lsp_types::{
+        CodeActionKind, DiagnosticRelatedInformation, Location, Position, TextEdit, WorkspaceEdit,
+    };
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range {
+            start: Position {
+                line,
+                character: start,
+            },
+            end: Position {
+                line,
+                character: end,
+            },
+        }
+    }
+
+    fn sample() -> (WorldPaths, Finding) {
+        let paths = WorldPaths {
+            world_dir: "world".to_string(),
+            root: PathBuf::from("/games/world"),
+        };
+        let uri = Url::parse("file:///games/world/triggers.amble").unwrap();
+        let rooms = Url::parse("file:///games/world/rooms.amble").unwrap();
+
+        let mut changes = HashMap::new();
+        changes.insert(
+            uri.clone(),
+            vec![TextEdit {
+                range: range(4, 9, 19),
+                new_text: "portal_gun".to_string(),
+            }],
+        );
+        let fix = CodeAction {
+            title: "Replace with `portal_gun`".to_string(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                ..WorkspaceEdit::default()
+            }),
+            ..CodeAction::default()
+        };
+        let diagnostic = Diagnostic {
+            range: range(4, 9, 19),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String("undefined-item".to_string())),
+            message: "Undefined item: 'portal_gnu'".to_string(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location {
+                    uri: rooms,
+                    range: range(0, 5, 9),
+                },
+                message: "Also named here".to_string(),
+            }]),
+            ..Diagnostic::default()
+        };
+
+        (
+            paths,
+            Finding::new(uri, diagnostic, vec![CodeActionOrCommand::CodeAction(fix)]),
+        )
+    }
+
+    #[test]
+    fn text_lines_are_one_based() {
+        let (paths, finding) = sample();
+        assert_eq!(
+            text_line(&paths, &finding),
+            "world/triggers.amble:5:10: warning: Undefined item: 'portal_gnu'"
+        );
+    }
+
+    #[test]
+    fn json_report_includes_rule_range_related_and_fixes() {
+        let (paths, finding) = sample();
+        let report = json_report(&paths, &[finding]);
+        let entry = &report[0];
+
+        assert_eq!(entry["file"], "world/triggers.amble");
+        assert_eq!(entry["rule"], "undefined-item");
+        assert_eq!(entry["severity"], "warning");
+        assert_eq!(entry["range"]["start"]["line"], 4);
+        assert_eq!(entry["related"][0]["file"], "world/rooms.amble");
+        assert_eq!(entry["fixes"][0]["edits"][0]["newText"], "portal_gun");
+    }
+
+    #[test]
+    fn sarif_report_uses_one_based_regions_and_root_relative_paths() {
+        let (paths, finding) = sample();
+        let report = sarif_report(&paths, &[finding]);
+        let run = &report["runs"][0];
+        let result = &run["results"][0];
+
+        assert_eq!(report["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "undefined-item");
+        assert_eq!(result["ruleId"], "undefined-item");
+        assert_eq!(result["level"], "warning");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "triggers.amble");
+        assert_eq!(location["region"]["startLine"], 5);
+        assert_eq!(location["region"]["startColumn"], 10);
+        assert_eq!(
+            result["relatedLocations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "rooms.amble"
+        );
+        assert_eq!(
+            result["fixes"][0]["artifactChanges"][0]["replacements"][0]["insertedContent"]["text"],
+            "portal_gun"
+        );
+    }
+}
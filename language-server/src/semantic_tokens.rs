@@ -0,0 +1,243 @@
+use crate::backend::Backend;
+use crate::symbols::{split_scoped_id, SymbolKind, SymbolOccurrence};
+use tower_lsp::lsp_types::{
+    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend, Url,
+};
+
+/// One token type per `SymbolKind`, in `token_type_index` order.
+const TOKEN_TYPES: &[&str] = &[
+    "room",
+    "item",
+    "npc",
+    "flag",
+    "set",
+    "condition",
+    "actionSet",
+    "spinner",
+    "goal",
+    "trigger",
+    "npcState",
+    "itemAbility",
+];
+
+const DEFINITION_MODIFIER: u32 = 1 << 0;
+const UNDEFINED_MODIFIER: u32 = 1 << 1;
+
+pub(crate) fn semantic_tokens_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES
+            .iter()
+            .map(|name| SemanticTokenType::new(name))
+            .collect(),
+        token_modifiers: vec![
+            SemanticTokenModifier::DEFINITION,
+            SemanticTokenModifier::new("undefined"),
+        ],
+    }
+}
+
+fn token_type_index(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Room => 0,
+        SymbolKind::Item => 1,
+        SymbolKind::Npc => 2,
+        SymbolKind::Flag => 3,
+        SymbolKind::Set => 4,
+        SymbolKind::Cond => 5,
+        SymbolKind::ActionSet => 6,
+        SymbolKind::Spinner => 7,
+        SymbolKind::Goal => 8,
+        SymbolKind::Trigger => 9,
+        SymbolKind::NpcState => 10,
+        SymbolKind::ItemAbility => 11,
+    }
+}
+
+impl Backend {
+    /// Tokens for every indexed id in `uri`, optionally limited to those overlapping `range`.
+    pub(crate) fn semantic_tokens(&self, uri: &Url, range: Option<Range>) -> Vec<SemanticToken> {
+        let Some(occurrences) = self.document_symbols.get(&uri.to_string()) else {
+            return Vec::new();
+        };
+
+        let mut tokens: Vec<(Range, u32, u32)> = occurrences
+            .iter()
+            // Ids never span lines; anything that does is a parse artefact.
+            .filter(|occurrence| occurrence.range.start.line == occurrence.range.end.line)
+            .filter(|occurrence| range.is_none_or(|range| overlaps(&occurrence.range, &range)))
+            .map(|occurrence| {
+                (
+                    occurrence.range,
+                    token_type_index(occurrence.kind),
+                    self.token_modifiers(uri, occurrence),
+                )
+            })
+            .collect();
+        tokens.sort_by_key(|(range, _, _)| (range.start.line, range.start.character));
+
+        encode_tokens(&tokens)
+    }
+
+    fn token_modifiers(&self, uri: &Url, occurrence: &SymbolOccurrence) -> u32 {
+        if self.is_definition_occurrence(uri, occurrence) {
+            return DEFINITION_MODIFIER;
+        }
+        if self.is_undefined(occurrence.kind, &occurrence.id) {
+            return UNDEFINED_MODIFIER;
+        }
+        0
+    }
+
+    /// Mirrors the undefined-reference diagnostics: room slots also accept set names, and
+    /// abilities are only unknown on items that exist.
+    fn is_undefined(&self, kind: SymbolKind, id: &str) -> bool {
+        match kind {
+            SymbolKind::Trigger | SymbolKind::NpcState => false,
+            SymbolKind::Room => {
+                !self.symbols.rooms.has_definition(id) && !self.symbols.sets.has_definition(id)
+            }
+            SymbolKind::ItemAbility => {
+                !self.symbols.item_abilities.has_definition(id)
+                    && split_scoped_id(id)
+                        .is_some_and(|(item_id, _)| self.symbols.items.has_definition(item_id))
+            }
+            _ => !self.symbols.index(kind).has_definition(id),
+        }
+    }
+}
+
+fn overlaps(a: &Range, b: &Range) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Delta-encodes sorted `(range, type, modifiers)` triples, dropping tokens that overlap the
+/// previous one since clients reject them.
+fn encode_tokens(tokens: &[(Range, u32, u32)]) -> Vec<SemanticToken> {
+    let mut encoded = Vec::with_capacity(tokens.len());
+    let mut previous_line = 0;
+    let mut previous_start = 0;
+    let mut previous_end: Option<(u32, u32)> = None;
+    for (range, token_type, modifiers) in tokens {
+        let (line, start) = (range.start.line, range.start.character);
+        if previous_end.is_some_and(|end| (line, start) < end) {
+            continue;
+        }
+        let length = range.end.character.saturating_sub(start);
+        if length == 0 {
+            continue;
+        }
+
+        let delta_line = line - previous_line;
+        let delta_start = if delta_line == 0 {
+            start - previous_start
+        } else {
+            start
+        };
+        encoded.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type: *token_type,
+            token_modifiers_bitset: *modifiers,
+        });
+        previous_line = line;
+        previous_start = start;
+        previous_end = Some((line, range.end.character));
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::LspService;
+
+    const SOURCE: &str = r#"room hall {
+    name "Hall"
+    desc "A hall."
+    exit north -> porch
+}
+
+room porch {
+    name "Porch"
+    desc "A porch."
+    exit south -> hall
+}
+
+trigger "Light lamp" when always {
+    do spawn item lamp into room cellar
+}
+"#;
+
+    #[test]
+    fn legend_has_a_type_for_every_symbol_kind() {
+        assert_eq!(
+            semantic_tokens_legend().token_types.len(),
+            TOKEN_TYPES.len()
+        );
+        assert_eq!(
+            token_type_index(SymbolKind::ItemAbility) as usize,
+            TOKEN_TYPES.len() - 1
+        );
+    }
+
+    #[test]
+    fn classifies_definitions_references_and_undefined_ids() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = Url::parse("file:///world/rooms.amble").unwrap();
+        backend.analyze_document(&uri, SOURCE);
+
+        let tokens = backend.semantic_tokens(&uri, None);
+        let mut line = 0;
+        let mut start = 0;
+        let mut decoded = Vec::new();
+        for token in &tokens {
+            line += token.delta_line;
+            start = if token.delta_line == 0 {
+                start + token.delta_start
+            } else {
+                token.delta_start
+            };
+            let text = &SOURCE.lines().nth(line as usize).unwrap()
+                [start as usize..(start + token.length) as usize];
+            decoded.push((
+                text.to_string(),
+                TOKEN_TYPES[token.token_type as usize],
+                token.token_modifiers_bitset,
+            ));
+        }
+
+        let expect = |text: &str, token_type: &'static str, modifiers: u32| {
+            (text.to_string(), token_type, modifiers)
+        };
+        assert_eq!(
+            decoded,
+            [
+                expect("hall", "room", DEFINITION_MODIFIER),
+                expect("porch", "room", 0),
+                expect("porch", "room", DEFINITION_MODIFIER),
+                expect("hall", "room", 0),
+                expect("\"Light lamp\"", "trigger", DEFINITION_MODIFIER),
+                expect("lamp", "item", UNDEFINED_MODIFIER),
+                expect("cellar", "room", UNDEFINED_MODIFIER),
+            ]
+        );
+
+        let exits_only = backend.semantic_tokens(
+            &uri,
+            Some(Range {
+                start: tower_lsp::lsp_types::Position {
+                    line: 3,
+                    character: 0,
+                },
+                end: tower_lsp::lsp_types::Position {
+                    line: 6,
+                    character: 0,
+                },
+            }),
+        );
+        assert_eq!(exits_only.len(), 1);
+        assert_eq!(exits_only[0].delta_line, 3);
+    }
+}
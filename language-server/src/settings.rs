@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Client settings, sent as `initializationOptions` and in `workspace/didChangeConfiguration`.
+/// In Zed they live under `lsp.amble-lsp.settings` (or `initialization_options`):
+///
+/// ```json
+/// { "inlayHints": { "roomNames": true, "itemNames": true, "npcNames": false, "flagLimits": true } }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct Settings {
+    pub inlay_hints: InlayHintSettings,
+}
+
+/// Which inlay hint categories to show; every category is on unless turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct InlayHintSettings {
+    pub room_names: bool,
+    pub item_names: bool,
+    pub npc_names: bool,
+    pub flag_limits: bool,
+}
+
+impl Default for InlayHintSettings {
+    fn default() -> Self {
+        Self {
+            room_names: true,
+            item_names: true,
+            npc_names: true,
+            flag_limits: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads a settings payload, which may also be nested under an `amble` key. Missing keys
+    /// keep their defaults; `None` if the payload isn't a settings object at all.
+    pub(crate) fn from_value(value: &Value) -> Option<Self> {
+        let value = value.get("amble").unwrap_or(value);
+        serde_json::from_value(value.clone()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        let settings =
+            Settings::from_value(&json!({ "inlayHints": { "npcNames": false } })).unwrap();
+        assert_eq!(
+            settings.inlay_hints,
+            InlayHintSettings {
+                npc_names: false,
+                ..InlayHintSettings::default()
+            }
+        );
+
+        let nested =
+            Settings::from_value(&json!({ "amble": { "inlayHints": { "flagLimits": false } } }))
+                .unwrap();
+        assert!(!nested.inlay_hints.flag_limits);
+        assert!(nested.inlay_hints.room_names);
+
+        assert_eq!(Settings::from_value(&json!({})), Some(Settings::default()));
+        assert_eq!(Settings::from_value(&json!("verbose")), None);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::backend::Backend;
+use crate::keywords::{is_word_char, scan_statement, Form, Token, FORMS};
+use tower_lsp::lsp_types::{
+    Documentation, ParameterInformation, ParameterLabel, Position, SignatureHelp,
+    SignatureInformation, Url,
+};
+
+/// One piece of a form's syntax; each matches exactly one typed token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Element {
+    Word(&'static str),
+    Punct(char),
+    /// An argument, by index into the form's parameters.
+    Slot {
+        index: usize,
+        quoted: bool,
+    },
+}
+
+/// An argument of a form: where it sits in `syntax` and what it expects.
+#[derive(Debug, PartialEq)]
+struct Parameter {
+    offsets: [u32; 2],
+    name: &'static str,
+    quoted: bool,
+}
+
+/// A form's arguments and every way of writing it, one per combination of optional parts.
+struct Signature {
+    parameters: Vec<Parameter>,
+    variants: Vec<Vec<Element>>,
+}
+
+fn parse_signature(syntax: &'static str) -> Signature {
+    // Required elements, plus optional groups that each double the variants.
+    let mut parts: Vec<(bool, Vec<Element>)> = Vec::new();
+    let mut parameters = Vec::new();
+    let mut optional: Option<Vec<Element>> = None;
+    let bytes = syntax.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        let element = match c {
+            _ if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '[' => {
+                optional = Some(Vec::new());
+                i += 1;
+                continue;
+            }
+            ']' => {
+                parts.push((true, optional.take().unwrap_or_default()));
+                i += 1;
+                continue;
+            }
+            '<' | '"' if syntax[i..].starts_with('<') || syntax[i..].starts_with("\"<") => {
+                let quoted = c == '"';
+                let open = if quoted { i + 1 } else { i };
+                let close = open + syntax[open..].find('>').unwrap_or(syntax.len() - open);
+                let end = if quoted { close + 2 } else { close + 1 }.min(syntax.len());
+                parameters.push(Parameter {
+                    offsets: [i as u32, end as u32],
+                    name: &syntax[open + 1..close],
+                    quoted,
+                });
+                i = end;
+                Element::Slot {
+                    index: parameters.len() - 1,
+                    quoted,
+                }
+            }
+            _ if is_word_char(c) => {
+                let end = syntax[i..]
+                    .find(|c: char| !is_word_char(c))
+                    .map_or(syntax.len(), |len| i + len);
+                let word = &syntax[i..end];
+                i = end;
+                Element::Word(word)
+            }
+            _ => {
+                i += 1;
+                Element::Punct(c)
+            }
+        };
+        match optional.as_mut() {
+            Some(group) => group.push(element),
+            None => parts.push((false, vec![element])),
+        }
+    }
+
+    let mut variants: Vec<Vec<Element>> = vec![Vec::new()];
+    for (is_optional, elements) in parts {
+        if is_optional {
+            let with: Vec<Vec<Element>> = variants
+                .iter()
+                .map(|variant| [variant.as_slice(), &elements].concat())
+                .collect();
+            variants.extend(with);
+        } else {
+            for variant in &mut variants {
+                variant.extend(&elements);
+            }
+        }
+    }
+    // Prefer the shortest spelling when several fit what's typed so far.
+    variants.sort_by_key(Vec::len);
+
+    Signature {
+        parameters,
+        variants,
+    }
+}
+
+fn element_matches(element: Element, token: Token, partial: bool) -> bool {
+    match (element, token) {
+        (Element::Word(keyword), Token::Word(word, _)) if partial => keyword.starts_with(word),
+        (Element::Word(keyword), Token::Word(word, _)) => keyword == word,
+        (Element::Punct(expected), Token::Punct(found)) => expected == found,
+        (Element::Slot { quoted: false, .. }, Token::Word(..)) => true,
+        (Element::Slot { quoted: true, .. }, Token::Str) => true,
+        _ => false,
+    }
+}
+
+/// The parameter the cursor is on once `tokens` are typed, or `None` if they don't fit
+/// `variant`. The inner `None` means the cursor is on a keyword.
+fn active_parameter(variant: &[Element], tokens: &[Token], partial: bool) -> Option<Option<usize>> {
+    if tokens.len() > variant.len() {
+        return None;
+    }
+    let fits = tokens
+        .iter()
+        .zip(variant)
+        .enumerate()
+        .all(|(index, (token, element))| {
+            element_matches(*element, *token, partial && index + 1 == tokens.len())
+        });
+    if !fits {
+        return None;
+    }
+
+    let slot = |element: &Element| match element {
+        Element::Slot { index, .. } => Some(*index),
+        _ => None,
+    };
+    if partial {
+        return Some(tokens.last().and_then(|_| slot(&variant[tokens.len() - 1])));
+    }
+    Some(variant[tokens.len()..].iter().find_map(slot))
+}
+
+/// What an argument expects, from its name in the syntax.
+fn parameter_kind(parameter: &Parameter) -> String {
+    if parameter.quoted {
+        return "text".to_string();
+    }
+    if let Some((_, choices)) = parameter.name.split_once(':') {
+        return format!("one of {}", choices.replace('|', ", "));
+    }
+    match parameter.name {
+        "room" => "room id",
+        "item" | "container" | "replacement" => "item id",
+        "npc" => "NPC id",
+        "flag" => "flag name",
+        "spinner" => "spinner id",
+        "state" => "NPC state",
+        "direction" => "exit direction",
+        "ability" | "target" => "ability name",
+        "interaction" => "interaction name",
+        "action set" => "action set name",
+        "amount" | "turns" | "turn" | "points" | "limit" | "width" | "percent" | "hp" | "uses" => {
+            "number"
+        }
+        other => other,
+    }
+    .to_string()
+}
+
+fn signature_information(
+    form: &Form,
+    signature: &Signature,
+    active: Option<usize>,
+) -> SignatureInformation {
+    SignatureInformation {
+        label: form.syntax.to_string(),
+        documentation: Some(Documentation::String(form.summary.to_string())),
+        parameters: Some(
+            signature
+                .parameters
+                .iter()
+                .map(|parameter| ParameterInformation {
+                    label: ParameterLabel::LabelOffsets(parameter.offsets),
+                    documentation: Some(Documentation::String(format!(
+                        "{}: {}",
+                        parameter.name.split(':').next().unwrap_or(parameter.name),
+                        parameter_kind(parameter)
+                    ))),
+                })
+                .collect(),
+        ),
+        // Past the last parameter, so nothing is highlighted while on a keyword.
+        active_parameter: Some(active.unwrap_or(signature.parameters.len()) as u32),
+    }
+}
+
+impl Backend {
+    /// Shapes of the action, condition or statement being typed at `position`, with the
+    /// argument under the cursor highlighted.
+    pub(crate) fn signature_help(&self, uri: &Url, position: Position) -> Option<SignatureHelp> {
+        let doc = self.documents.get(&uri.to_string())?;
+        let offset = doc.offset(position)?;
+        let statement = scan_statement(doc.text(), offset)?;
+
+        let signatures: Vec<SignatureInformation> = FORMS
+            .iter()
+            .filter(|form| form.scope == statement.scope)
+            .filter_map(|form| {
+                // Wait for the leading keywords before guessing at a shape.
+                let keywords = form.keywords().len();
+                let typed = statement.tokens.len();
+                if typed < keywords || (typed == keywords && statement.partial) {
+                    return None;
+                }
+                let signature = parse_signature(form.syntax);
+                if signature.parameters.is_empty() {
+                    return None;
+                }
+                let active = signature.variants.iter().find_map(|variant| {
+                    active_parameter(variant, &statement.tokens, statement.partial)
+                })?;
+                Some(signature_information(form, &signature, active))
+            })
+            .collect();
+
+        if signatures.is_empty() {
+            return None;
+        }
+        Some(SignatureHelp {
+            signatures,
+            active_signature: Some(0),
+            active_parameter: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::LspService;
+
+    #[test]
+    fn every_form_has_well_formed_parameters() {
+        for form in FORMS {
+            let signature = parse_signature(form.syntax);
+            for parameter in &signature.parameters {
+                let [start, end] = parameter.offsets;
+                let label = &form.syntax[start as usize..end as usize];
+                assert!(
+                    label.starts_with(['<', '"']) && label.ends_with(['>', '"']),
+                    "bad parameter `{}` in `{}`",
+                    label,
+                    form.syntax
+                );
+            }
+        }
+
+        let damage =
+            parse_signature("damage npc <npc> <amount> [for <turns> turns] cause \"<cause>\"");
+        assert_eq!(damage.parameters.len(), 4);
+        assert_eq!(damage.variants.len(), 2);
+        assert_eq!(damage.variants[0].len(), 6);
+        assert_eq!(damage.variants[1].len(), 9);
+    }
+
+    /// The label of the highlighted parameter of each signature offered at `|`.
+    fn highlighted(backend: &Backend, source: &str) -> Vec<(String, Option<String>)> {
+        let uri = Url::parse("file:///world/triggers.amble").unwrap();
+        let offset = source.find('|').unwrap();
+        let text = source.replace('|', "");
+        backend.analyze_document(&uri, &text);
+        let line = text[..offset].matches('\n').count() as u32;
+        let character = (offset - text[..offset].rfind('\n').map_or(0, |i| i + 1)) as u32;
+
+        let Some(help) = backend.signature_help(&uri, Position::new(line, character)) else {
+            return Vec::new();
+        };
+        help.signatures
+            .into_iter()
+            .map(|signature| {
+                let active = signature.active_parameter.unwrap() as usize;
+                let label = signature.parameters.unwrap().get(active).map(|parameter| {
+                    let ParameterLabel::LabelOffsets([start, end]) = parameter.label else {
+                        unreachable!()
+                    };
+                    signature.label[start as usize..end as usize].to_string()
+                });
+                (signature.label, label)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn highlights_the_argument_under_the_cursor() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let barred = "set barred message from <room> to <room> \"<message>\"".to_string();
+
+        let in_trigger = |line: &str| format!("trigger \"t\" when always {{\n    {}\n}}\n", line);
+        assert_eq!(
+            highlighted(backend, &in_trigger("do set barred message from |")),
+            [(barred.clone(), Some("<room>".to_string()))]
+        );
+        assert_eq!(
+            highlighted(
+                backend,
+                &in_trigger("do set barred message from hall to porch \"Loc|")
+            ),
+            [(barred.clone(), Some("\"<message>\"".to_string()))]
+        );
+        assert_eq!(
+            highlighted(backend, &in_trigger("do set barred message from hall to |")),
+            [(barred, Some("<room>".to_string()))]
+        );
+
+        let damage = "damage npc <npc> <amount> [for <turns> turns] cause \"<cause>\"".to_string();
+        assert_eq!(
+            highlighted(backend, &in_trigger("do damage npc guard 3 |")),
+            [(damage.clone(), Some("\"<cause>\"".to_string()))]
+        );
+        assert_eq!(
+            highlighted(backend, &in_trigger("do damage npc guard 3 for |")),
+            [(damage.clone(), Some("<turns>".to_string()))]
+        );
+        assert_eq!(
+            highlighted(backend, &in_trigger("do damage npc guard 3 fo|")),
+            [(damage, None)]
+        );
+
+        assert_eq!(
+            highlighted(backend, &in_trigger("if npc in state guard |")),
+            [(
+                "npc in state <npc> <state>".to_string(),
+                Some("<state>".to_string())
+            )]
+        );
+        assert!(highlighted(backend, &in_trigger("do spaw|")).is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns up to three defined ids closest to `target`, closest first.
+///
+/// Ids are compared with case and `-`/`_` separators folded away, so `portal-gun`, `portal_gun`
+/// and `portalGun` all count as the same spelling; the raw distance only breaks ties.
+pub(crate) fn suggest_similar_ids(
+    target: &str,
+    candidates: impl IntoIterator<Item = String>,
+) -> Vec<String> {
+    let folded_target = fold_id(target);
+    let max_distance = (folded_target.chars().count() / 3).max(1);
+
+    let mut scored: Vec<(usize, usize, String)> = candidates
+        .into_iter()
+        .filter(|candidate| candidate != target)
+        .filter_map(|candidate| {
+            let distance = edit_distance(&folded_target, &fold_id(&candidate));
+            (distance <= max_distance)
+                .then(|| (distance, edit_distance(target, &candidate), candidate))
+        })
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.2 == b.2);
+
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, _, candidate)| candidate)
+        .collect()
+}
+
+/// Formats the " (did you mean 'a' or 'b'?)" suffix for an undefined-reference message.
+pub(crate) fn did_you_mean(suggestions: &[String]) -> String {
+    let quoted: Vec<String> = suggestions.iter().map(|id| format!("'{}'", id)).collect();
+    match quoted.as_slice() {
+        [] => String::new(),
+        [only] => format!(" (did you mean {}?)", only),
+        [rest @ .., last] => format!(" (did you mean {} or {}?)", rest.join(", "), last),
+    }
+}
+
+fn fold_id(id: &str) -> String {
+    id.chars()
+        .filter(|ch| !matches!(ch, '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Edit distance over chars where swapping two adjacent chars (`gnu` for `gun`) costs one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a_chars: Vec<char> = a.chars().collect();
+    let b_chars: Vec<char> = b.chars().collect();
+    let width = b_chars.len() + 1;
+    let mut table = vec![0; (a_chars.len() + 1) * width];
+    for (j, cell) in table.iter_mut().take(width).enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a_chars.len() {
+        table[i * width] = i;
+        for j in 1..=b_chars.len() {
+            let cost = usize::from(a_chars[i - 1] != b_chars[j - 1]);
+            let mut best = (table[(i - 1) * width + j - 1] + cost)
+                .min(table[(i - 1) * width + j] + 1)
+                .min(table[i * width + j - 1] + 1);
+            if i > 1
+                && j > 1
+                && a_chars[i - 1] == b_chars[j - 2]
+                && a_chars[i - 2] == b_chars[j - 1]
+            {
+                best = best.min(table[(i - 2) * width + j - 2] + 1);
+            }
+            table[i * width + j] = best;
+        }
+    }
+
+    table[a_chars.len() * width + b_chars.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn suggests_closest_ids_ignoring_case_and_separators() {
+        let candidates = ids(&[
+            "portal_gun",
+            "portal-gun-2",
+            "potted_plant",
+            "turnOn",
+            "lamp",
+        ]);
+
+        assert_eq!(
+            suggest_similar_ids("portal_gnu", candidates.clone()),
+            ids(&["portal_gun", "portal-gun-2"])
+        );
+        assert_eq!(
+            suggest_similar_ids("portal-gun", candidates.clone()),
+            ids(&["portal_gun", "portal-gun-2"])
+        );
+        assert_eq!(
+            suggest_similar_ids("turn_on", candidates.clone()),
+            ids(&["turnOn"])
+        );
+        assert!(suggest_similar_ids("xyzzy", candidates).is_empty());
+    }
+
+    #[test]
+    fn counts_adjacent_swaps_as_one_edit() {
+        assert_eq!(edit_distance("gnu", "gun"), 1);
+        assert_eq!(edit_distance("lamp", "lamp"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn formats_did_you_mean_suffix() {
+        assert_eq!(did_you_mean(&[]), "");
+        assert_eq!(did_you_mean(&ids(&["a"])), " (did you mean 'a'?)");
+        assert_eq!(
+            did_you_mean(&ids(&["a", "b", "c"])),
+            " (did you mean 'a', 'b' or 'c'?)"
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use std::collections::HashSet;
 use tower_lsp::lsp_types::{Range, Url};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -11,6 +12,11 @@ pub enum SymbolKind {
     Set,
     Cond,
     ActionSet,
+    Spinner,
+    Goal,
+    Trigger,
+    NpcState,
+    ItemAbility,
 }
 
 impl SymbolKind {
@@ -23,6 +29,11 @@ impl SymbolKind {
             SymbolKind::Set => "Set",
             SymbolKind::Cond => "Condition Alias",
             SymbolKind::ActionSet => "Action Set",
+            SymbolKind::Spinner => "Spinner",
+            SymbolKind::Goal => "Goal",
+            SymbolKind::Trigger => "Trigger",
+            SymbolKind::NpcState => "NPC State",
+            SymbolKind::ItemAbility => "Item Ability",
         }
     }
 }
@@ -61,13 +72,30 @@ pub enum SymbolMetadata {
     Set(SetMetadata),
     Cond(CondMetadata),
     ActionSet(ActionSetMetadata),
+    Spinner(SpinnerMetadata),
+    Goal(GoalMetadata),
+    Trigger(TriggerMetadata),
+    NpcState(NpcStateMetadata),
+    ItemAbility(ItemAbilityMetadata),
 }
 
 #[derive(Debug, Clone)]
 pub struct RoomMetadata {
     pub name: Option<String>,
     pub description: Option<String>,
-    pub exits: Vec<String>,
+    pub exits: Vec<RoomExit>,
+}
+
+/// One `exit <dir> -> <room> { ... }` line of a room, options included.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoomExit {
+    pub direction: String,
+    pub to: String,
+    pub hidden: bool,
+    pub locked: bool,
+    pub barred: Option<String>,
+    pub required_flags: Vec<String>,
+    pub required_items: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -87,6 +115,7 @@ pub struct NpcMetadata {
     pub description: Option<String>,
     pub location: Option<String>,
     pub state: Option<String>,
+    pub dialogue_states: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -110,6 +139,68 @@ pub struct ActionSetMetadata {
     pub body: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct SpinnerMetadata {
+    pub wedge_count: usize,
+    pub total_width: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GoalMetadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub group: Option<String>,
+    pub start_condition: Option<String>,
+    pub done_condition: Option<String>,
+    pub fail_condition: Option<String>,
+    /// Parsed forms of the conditions above.
+    pub start: Option<GoalCondition>,
+    pub done: Option<GoalCondition>,
+    pub fail: Option<GoalCondition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoalConditionKind {
+    HasFlag,
+    MissingFlag,
+    HasItem,
+    ReachedRoom,
+    GoalComplete,
+    FlagInProgress,
+    FlagComplete,
+}
+
+/// A `start`, `done` or `fail when` condition; `range` covers the id it names. Flag ids are
+/// reduced to their base (`quest#2` is `quest`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalCondition {
+    pub kind: GoalConditionKind,
+    pub id: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone)]
+pub struct TriggerMetadata {
+    pub event: Option<String>,
+    pub only_once: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct NpcStateMetadata {
+    pub npc_id: String,
+    pub state: String,
+    pub custom: bool,
+    pub line_count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemAbilityMetadata {
+    pub item_id: String,
+    pub ability: String,
+    pub target: Option<String>,
+    pub added_by_patch: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct SymbolOccurrence {
     pub kind: SymbolKind,
@@ -129,51 +220,52 @@ pub struct SymbolIndex {
     definitions: DashMap<String, SymbolDefinition>,
     duplicates: DashMap<String, Vec<SymbolDefinition>>,
     references: DashMap<String, Vec<SymbolReference>>,
+    /// Ids each document has defined or referenced, so clearing a document only visits those.
+    document_ids: DashMap<Url, HashSet<String>>,
 }
 
 impl SymbolIndex {
     pub fn clear_document(&self, uri: &Url) {
-        let mut removed_ids = Vec::new();
-        self.definitions.retain(|id, def| {
-            if def.location.uri == *uri {
-                removed_ids.push(id.clone());
-                false
-            } else {
-                true
-            }
-        });
-        for mut entry in self.references.iter_mut() {
-            entry
-                .value_mut()
-                .retain(|reference| reference.location.uri != *uri);
-        }
-        for mut entry in self.duplicates.iter_mut() {
-            entry
-                .value_mut()
-                .retain(|definition| definition.location.uri != *uri);
-        }
-        self.duplicates.retain(|_, defs| !defs.is_empty());
-
-        for id in removed_ids {
-            if let Some(mut extra) = self.duplicates.get_mut(&id) {
-                let mut promoted = None;
-                if !extra.value().is_empty() {
-                    let new_def = extra.value_mut().remove(0);
-                    promoted = Some(new_def);
-                }
-                let should_remove = extra.value().is_empty();
-                drop(extra);
-                if let Some(definition) = promoted {
-                    self.definitions.insert(id.clone(), definition);
-                }
-                if should_remove {
-                    self.duplicates.remove(&id);
+        let Some((_, ids)) = self.document_ids.remove(uri) else {
+            return;
+        };
+
+        for id in ids {
+            let removed_definition = self
+                .definitions
+                .remove_if(&id, |_, def| def.location.uri == *uri)
+                .is_some();
+            self.references.remove_if_mut(&id, |_, refs| {
+                refs.retain(|reference| reference.location.uri != *uri);
+                refs.is_empty()
+            });
+            self.duplicates.remove_if_mut(&id, |_, defs| {
+                defs.retain(|definition| definition.location.uri != *uri);
+                defs.is_empty()
+            });
+
+            if removed_definition {
+                if let Some(mut extra) = self.duplicates.get_mut(&id) {
+                    let mut promoted = None;
+                    if !extra.value().is_empty() {
+                        let new_def = extra.value_mut().remove(0);
+                        promoted = Some(new_def);
+                    }
+                    let should_remove = extra.value().is_empty();
+                    drop(extra);
+                    if let Some(definition) = promoted {
+                        self.definitions.insert(id.clone(), definition);
+                    }
+                    if should_remove {
+                        self.duplicates.remove(&id);
+                    }
                 }
             }
         }
     }
 
     pub fn insert_definition(&self, id: String, def: SymbolDefinition) {
+        self.record_document_id(&def.location.uri, &id);
         match self.definitions.entry(id.clone()) {
             Entry::Occupied(_) => {
                 self.duplicates.entry(id).or_insert_with(Vec::new).push(def);
@@ -186,12 +278,26 @@ impl SymbolIndex {
     }
 
     pub fn add_reference(&self, id: String, reference: SymbolReference) {
+        self.record_document_id(&reference.location.uri, &id);
         self.references
             .entry(id)
             .or_insert_with(Vec::new)
             .push(reference);
     }
 
+    fn record_document_id(&self, uri: &Url, id: &str) {
+        if let Some(mut ids) = self.document_ids.get_mut(uri) {
+            if !ids.contains(id) {
+                ids.insert(id.to_string());
+            }
+            return;
+        }
+        self.document_ids
+            .entry(uri.clone())
+            .or_default()
+            .insert(id.to_string());
+    }
+
     pub fn definition(
         &self,
         id: &str,
@@ -206,6 +312,13 @@ impl SymbolIndex {
         self.references.get(id)
     }
 
+    pub fn duplicates(
+        &self,
+        id: &str,
+    ) -> Option<dashmap::mapref::one::Ref<'_, String, Vec<SymbolDefinition>>> {
+        self.duplicates.get(id)
+    }
+
     pub fn has_definition(&self, id: &str) -> bool {
         self.definitions.contains_key(id)
     }
@@ -234,6 +347,11 @@ pub struct SymbolStore {
     pub sets: SymbolIndex,
     pub conds: SymbolIndex,
     pub action_sets: SymbolIndex,
+    pub spinners: SymbolIndex,
+    pub goals: SymbolIndex,
+    pub triggers: SymbolIndex,
+    pub npc_states: SymbolIndex,
+    pub item_abilities: SymbolIndex,
 }
 
 impl SymbolStore {
@@ -246,6 +364,11 @@ impl SymbolStore {
             SymbolKind::Set => &self.sets,
             SymbolKind::Cond => &self.conds,
             SymbolKind::ActionSet => &self.action_sets,
+            SymbolKind::Spinner => &self.spinners,
+            SymbolKind::Goal => &self.goals,
+            SymbolKind::Trigger => &self.triggers,
+            SymbolKind::NpcState => &self.npc_states,
+            SymbolKind::ItemAbility => &self.item_abilities,
         }
     }
 
@@ -257,9 +380,29 @@ impl SymbolStore {
         self.sets.clear_document(uri);
         self.conds.clear_document(uri);
         self.action_sets.clear_document(uri);
+        self.spinners.clear_document(uri);
+        self.goals.clear_document(uri);
+        self.triggers.clear_document(uri);
+        self.npc_states.clear_document(uri);
+        self.item_abilities.clear_document(uri);
     }
 }
 
+/// NPC states are only meaningful per NPC, so the `npc_states` index keys them as `npc::state`.
+pub fn npc_state_id(npc_id: &str, state: &str) -> String {
+    format!("{}::{}", npc_id, state)
+}
+
+/// Abilities are keyed per item like NPC states; the engine matches ability names case-insensitively.
+pub fn item_ability_id(item_id: &str, ability: &str) -> String {
+    format!("{}::{}", item_id, ability.to_ascii_lowercase())
+}
+
+/// Splits an `owner::name` key produced by `npc_state_id` or `item_ability_id`.
+pub fn split_scoped_id(id: &str) -> Option<(&str, &str)> {
+    id.split_once("::")
+}
+
 pub(crate) fn sanitize_markdown(value: &str) -> String {
     value.trim().replace('|', "\\|").replace('\n', "<br>")
 }
@@ -313,4 +456,36 @@ mod tests {
             Url::parse("file:///rooms/b.amble").unwrap()
         );
     }
+
+    #[test]
+    fn clearing_a_document_leaves_other_documents_alone() {
+        let index = SymbolIndex::default();
+        index.insert_definition("room_a".into(), room_definition("rooms/a.amble"));
+        index.insert_definition("room_b".into(), room_definition("rooms/b.amble"));
+        for path in ["triggers/a.amble", "triggers/b.amble"] {
+            index.add_reference(
+                "room_a".into(),
+                SymbolReference {
+                    location: test_location(path),
+                    raw_id: "room_a".into(),
+                },
+            );
+        }
+
+        index.clear_document(&Url::parse("file:///triggers/a.amble").unwrap());
+        index.clear_document(&Url::parse("file:///rooms/b.amble").unwrap());
+
+        assert!(index.has_definition("room_a"));
+        assert!(!index.has_definition("room_b"));
+        let refs = index.references("room_a").unwrap();
+        assert_eq!(refs.len(), 1);
+        assert_eq!(
+            refs[0].location.uri,
+            Url::parse("file:///triggers/b.amble").unwrap()
+        );
+        drop(refs);
+
+        index.clear_document(&Url::parse("file:///triggers/b.amble").unwrap());
+        assert!(index.references("room_a").is_none());
+    }
 }
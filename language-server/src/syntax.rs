@@ -0,0 +1,375 @@
+use crate::text::Document;
+use tower_lsp::lsp_types::Range;
+use tree_sitter::Node;
+
+const TOKEN_PREVIEW_MAX_CHARS: usize = 24;
+
+/// A parse error surfaced by tree-sitter, grouped with the follow-on errors it caused.
+#[derive(Debug, Clone)]
+pub(crate) struct SyntaxError {
+    pub range: Range,
+    pub message: String,
+    pub related: Vec<(Range, String)>,
+}
+
+/// Tokens that must follow a given leaf, used to explain errors like `exit north hall`.
+const EXPECTED_AFTER: &[(&str, &str, &str)] = &[
+    ("exit_dir", "`->`", "exit direction"),
+    ("->", "a room id", "`->`"),
+    ("do", "an action", "`do`"),
+    ("if", "a condition", "`if`"),
+    ("when", "a trigger event", "`when`"),
+    ("priority", "a number", "`priority`"),
+];
+
+/// Collects every `ERROR` and `MISSING` node, folding errors inside the same top-level
+/// definition into a single diagnostic so one bad brace doesn't flood the document.
+pub(crate) fn collect_syntax_errors(
+    document: &Document,
+    root: Node,
+    text: &str,
+) -> Vec<SyntaxError> {
+    if !root.has_error() {
+        return Vec::new();
+    }
+
+    let mut groups: Vec<(usize, Vec<(Range, String)>)> = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let issue = if node.is_error() {
+            Some(describe_error(document, node, text))
+        } else if node.is_missing() {
+            Some(describe_missing(document, node))
+        } else {
+            None
+        };
+
+        if let Some(issue) = issue {
+            let group_id = top_level_ancestor(node).id();
+            match groups.iter_mut().find(|(id, _)| *id == group_id) {
+                Some((_, issues)) => issues.push(issue),
+                None => groups.push((group_id, vec![issue])),
+            }
+            continue;
+        }
+
+        if !node.has_error() {
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        for child in children.into_iter().rev() {
+            stack.push(child);
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, mut issues)| {
+            let (range, mut message) = issues.remove(0);
+            if !issues.is_empty() {
+                message = format!(
+                    "{} (+{} related syntax error{})",
+                    message,
+                    issues.len(),
+                    if issues.len() == 1 { "" } else { "s" }
+                );
+            }
+            SyntaxError {
+                range,
+                message,
+                related: issues,
+            }
+        })
+        .collect()
+}
+
+fn describe_missing(document: &Document, node: Node) -> (Range, String) {
+    let range = range_from_node(document, &node);
+    let expected = describe_token(node.kind(), node.is_named());
+    let context = enclosing_context(node);
+
+    let message = match (node.kind(), previous_token(node)) {
+        ("}" | ")", _) => match context {
+            Some(context) => format!("expected {} to close {}", expected, context),
+            None => format!("expected {}", expected),
+        },
+        (_, Some(prev)) => format!(
+            "expected {} after {}",
+            expected,
+            describe_token(prev.kind(), prev.is_named())
+        ),
+        (_, None) => match context {
+            Some(context) => format!("expected {} in {}", expected, context),
+            None => format!("expected {}", expected),
+        },
+    };
+
+    (range, message)
+}
+
+fn describe_error(document: &Document, node: Node, text: &str) -> (Range, String) {
+    let leaves = collect_leaves(node);
+    // A keyword like `do` only says what should come next, so when the error starts with or
+    // right after one, blame what follows it; otherwise the first identifier, or failing that
+    // the first leaf.
+    let before = previous_token(node).filter(|prev| expected_after(prev.kind()).is_some());
+    let offender_idx = match leaves.first() {
+        Some(_) if before.is_some() => Some(0),
+        Some(first) if leaves.len() > 1 && expected_after(first.kind()).is_some() => Some(1),
+        _ => leaves
+            .iter()
+            .position(|leaf| leaf.kind() == "identifier")
+            .or(if leaves.is_empty() { None } else { Some(0) }),
+    };
+    let Some(offender_idx) = offender_idx else {
+        let context = enclosing_context(node).unwrap_or("file");
+        return (
+            first_line_range(document, &node, text),
+            format!("syntax error in {}", context),
+        );
+    };
+
+    let offender = leaves[offender_idx];
+    let range = range_from_node(document, &offender);
+    let found = token_preview(&text[offender.byte_range()]);
+
+    if leaves.first().map(|leaf| leaf.kind()) == Some("trigger") && offender_idx > 0 {
+        let prev = leaves[offender_idx - 1];
+        if matches!(prev.kind(), "string" | "entity_name") {
+            return (
+                range,
+                format!("expected `when` after trigger name, found `{}`", found),
+            );
+        }
+    }
+
+    let previous = match offender_idx {
+        0 => before,
+        _ => Some(leaves[offender_idx - 1]),
+    };
+    if let Some(previous) = previous {
+        if let Some((expected, label)) = expected_after(previous.kind()) {
+            return (
+                range,
+                format!("expected {} after {}, found `{}`", expected, label, found),
+            );
+        }
+    }
+
+    let context = enclosing_context(node).unwrap_or("file");
+    (range, format!("unexpected `{}` in {}", found, context))
+}
+
+/// What must follow a leaf of `kind`, and how to name that leaf, per `EXPECTED_AFTER`.
+fn expected_after(kind: &str) -> Option<(&'static str, &'static str)> {
+    EXPECTED_AFTER
+        .iter()
+        .find(|(after, _, _)| *after == kind)
+        .map(|(_, expected, label)| (*expected, *label))
+}
+
+fn collect_leaves(node: Node) -> Vec<Node> {
+    let mut leaves = Vec::new();
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        if current.child_count() == 0 {
+            if current.start_byte() < current.end_byte() && current.kind() != "comment" {
+                leaves.push(current);
+            }
+            continue;
+        }
+        let mut cursor = current.walk();
+        let children: Vec<Node> = current.children(&mut cursor).collect();
+        for child in children.into_iter().rev() {
+            stack.push(child);
+        }
+    }
+    leaves
+}
+
+fn top_level_ancestor(node: Node) -> Node {
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        if parent.parent().is_none() {
+            return current;
+        }
+        current = parent;
+    }
+    current
+}
+
+/// Finds the sibling token immediately before `node`, climbing out of alias wrappers.
+fn previous_token(node: Node) -> Option<Node> {
+    let mut current = node;
+    loop {
+        if let Some(prev) = current.prev_sibling() {
+            return Some(prev);
+        }
+        current = current.parent()?;
+    }
+}
+
+fn enclosing_context(node: Node) -> Option<&'static str> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if let Some(description) = describe_kind(parent.kind()) {
+            return Some(description);
+        }
+        current = parent.parent();
+    }
+    None
+}
+
+fn describe_kind(kind: &str) -> Option<&'static str> {
+    let description = match kind {
+        "source_file" => "file",
+        "room_def" | "room_block" => "room block",
+        "room_exit" => "exit",
+        "exit_block" => "exit options",
+        "item_def" | "item_block" => "item block",
+        "consumable_block" => "consumable block",
+        "npc_def" | "npc_block" => "NPC block",
+        "npc_dialogue_block" => "dialogue block",
+        "trigger_def" => "trigger",
+        "trigger_block" | "cond_body" | "cond_block" => "trigger block",
+        "goal_def" | "goal_block" => "goal block",
+        "spinner_def" | "spinner_block" => "spinner block",
+        "action_set_decl" | "action_set_block" => "action set",
+        "schedule_block" => "schedule block",
+        "item_patch_block" => "item patch",
+        "room_patch_block" => "room patch",
+        "npc_patch_block" => "NPC patch",
+        "game_def" | "game_block" => "game block",
+        "player_block" => "player block",
+        "scoring_block" => "scoring block",
+        "set_list" => "set list",
+        "cond_any_group" | "cond_all_group" => "condition group",
+        "overlay_stmt" | "ovl_block" => "overlay",
+        _ => return None,
+    };
+    Some(description)
+}
+
+fn describe_token(kind: &str, named: bool) -> String {
+    if !named {
+        return format!("`{}`", kind);
+    }
+    match kind {
+        "identifier" => "an identifier".to_string(),
+        "entity_name" | "entity_desc" | "string" | "player_message" => "a string".to_string(),
+        "number" | "pos_int" => "a number".to_string(),
+        "npc_id" => "an NPC id".to_string(),
+        "item_id" => "an item id".to_string(),
+        "exit_dir" => "exit direction".to_string(),
+        _ => format!("a {}", kind.trim_start_matches('_').replace('_', " ")),
+    }
+}
+
+fn token_preview(raw: &str) -> String {
+    let first_line = raw.lines().next().unwrap_or("").trim();
+    if first_line.chars().count() <= TOKEN_PREVIEW_MAX_CHARS {
+        first_line.to_string()
+    } else {
+        let truncated: String = first_line.chars().take(TOKEN_PREVIEW_MAX_CHARS).collect();
+        format!("{}...", truncated)
+    }
+}
+
+/// Clamps a multi-line error node to its first line so the squiggle stays readable.
+fn first_line_range(document: &Document, node: &Node, text: &str) -> Range {
+    let start = node.start_byte();
+    let line_end = text[start..]
+        .find('\n')
+        .map(|idx| start + idx)
+        .unwrap_or(text.len());
+    Range {
+        start: document.position_at(start),
+        end: document.position_at(node.end_byte().min(line_end)),
+    }
+}
+
+fn range_from_node(document: &Document, node: &Node) -> Range {
+    Range {
+        start: document.position_at(node.start_byte()),
+        end: document.position_at(node.end_byte()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree_sitter::Parser;
+
+    fn errors_for(source: &str) -> Vec<SyntaxError> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_amble::language())
+            .expect("load amble grammar");
+        let tree = parser.parse(source, None).expect("parse source");
+        let document = Document::new(source.to_string());
+        collect_syntax_errors(&document, tree.root_node(), source)
+    }
+
+    #[test]
+    fn clean_documents_report_nothing() {
+        let source = "room a {\n    name \"A\"\n    desc \"Room\"\n    exit north -> b\n}\n";
+        assert!(errors_for(source).is_empty());
+    }
+
+    #[test]
+    fn explains_missing_exit_arrow() {
+        let source = "room a {\n    name \"A\"\n    exit north b\n}\n";
+        let errors = errors_for(source);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .message
+            .starts_with("expected `->` after exit direction"));
+        assert_eq!(errors[0].range.start.line, 2);
+        assert_eq!(errors[0].range.start.character, 15);
+    }
+
+    #[test]
+    fn reports_missing_tokens_with_context() {
+        let source = "item x {\n  name \"X\"\n  movability free\n";
+        let errors = errors_for(source);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "expected `}` to close item block");
+    }
+
+    #[test]
+    fn reports_unknown_actions() {
+        let source = "trigger \"t\" when enter room a {\n    do shw \"x\"\n}\n";
+        let errors = errors_for(source);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("`shw`"));
+        assert_eq!(errors[0].range.start.line, 1);
+    }
+
+    #[test]
+    fn groups_cascading_errors_per_definition() {
+        let source = "room a {\n    exit north b\n    name \"A\"\n    exit south ->\n}\n\nroom e {\n    exit west f\n}\n";
+        let errors = errors_for(source);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.ends_with("(+1 related syntax error)"));
+        assert_eq!(errors[0].related.len(), 1);
+        assert_eq!(errors[0].related[0].1, "expected a room id after `->`");
+        assert!(errors[1].related.is_empty());
+    }
+
+    #[test]
+    fn blames_the_action_after_do() {
+        for source in [
+            "trigger \"t\" when enter room a {\n    do show \"x\"\n    do award points 3\n}\n",
+            "trigger \"t\" when enter room a {\n    do award points 3\n    do show \"x\"\n}\n",
+        ] {
+            let errors = errors_for(source);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(
+                errors[0].message,
+                "expected an action after `do`, found `award`"
+            );
+            assert_eq!(errors[0].range.start.character, 7);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use dashmap::DashMap;
-use tower_lsp::lsp_types::{Position, Range};
+use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+use tree_sitter::{InputEdit, Point, Tree};
 
 pub type DocumentStore = DashMap<String, Document>;
 
@@ -9,18 +10,78 @@ type LineOffset = usize;
 pub struct Document {
     text: String,
     line_index: LineIndex,
+    /// Syntax tree from the last parse, kept in step with `text` so re-parses are incremental.
+    tree: Option<Tree>,
 }
 
 impl Document {
     pub fn new(text: String) -> Self {
         let line_index = LineIndex::new(&text);
-        Self { text, line_index }
+        Self {
+            text,
+            line_index,
+            tree: None,
+        }
     }
 
     pub fn text(&self) -> &str {
         &self.text
     }
 
+    pub fn tree(&self) -> Option<&Tree> {
+        self.tree.as_ref()
+    }
+
+    pub fn set_tree(&mut self, tree: Tree) {
+        self.tree = Some(tree);
+    }
+
+    /// Applies one `didChange` edit, keeping the line index and cached tree in sync.
+    /// A change without a range replaces the whole document and drops the tree.
+    pub fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
+        let Some(range) = change.range else {
+            *self = Document::new(change.text);
+            return;
+        };
+
+        let start = self.clamped_offset(range.start);
+        let old_end = self.clamped_offset(range.end).max(start);
+        let start_position = self.line_index.point_at(start);
+        let old_end_position = self.line_index.point_at(old_end);
+
+        self.text.replace_range(start..old_end, &change.text);
+        self.line_index.apply_edit(start, old_end, &change.text);
+
+        let new_end = start + change.text.len();
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(&InputEdit {
+                start_byte: start,
+                old_end_byte: old_end,
+                new_end_byte: new_end,
+                start_position,
+                old_end_position,
+                new_end_position: self.line_index.point_at(new_end),
+            });
+        }
+    }
+
+    /// Like `offset`, but positions past the end of a line or the document snap to that end,
+    /// as the LSP spec asks for edit ranges.
+    fn clamped_offset(&self, position: Position) -> usize {
+        if let Some(offset) = self.offset(position) {
+            return offset;
+        }
+        let next_line = Position {
+            line: position.line.saturating_add(1),
+            character: 0,
+        };
+        match self.offset(next_line) {
+            Some(next_start) if self.text[..next_start].ends_with("\r\n") => next_start - 2,
+            Some(next_start) => next_start.saturating_sub(1),
+            None => self.text.len(),
+        }
+    }
+
     pub fn offset(&self, position: Position) -> Option<usize> {
         self.line_index.offset(&self.text, position)
     }
@@ -116,6 +177,30 @@ impl LineIndex {
         }
     }
 
+    /// Replaces the line starts inside `start..old_end` with those of `new_text` and shifts
+    /// the rest, so an edit costs the lines after it rather than a rescan of the whole text.
+    fn apply_edit(&mut self, start: usize, old_end: usize, new_text: &str) {
+        let first_replaced = self.line_starts.partition_point(|&line| line <= start);
+        let first_kept = self.line_starts.partition_point(|&line| line <= old_end);
+        let inserted = new_text.match_indices('\n').map(|(idx, _)| start + idx + 1);
+        let shifted: Vec<LineOffset> = self.line_starts[first_kept..]
+            .iter()
+            .map(|&line| line - old_end + start + new_text.len())
+            .collect();
+        self.line_starts
+            .splice(first_replaced.., inserted.chain(shifted));
+    }
+
+    /// Tree-sitter position (row, byte column) of `offset`.
+    fn point_at(&self, offset: usize) -> Point {
+        let row = self.line_for_offset(offset);
+        let line_start = self.line_starts.get(row).copied().unwrap_or(0);
+        Point {
+            row,
+            column: offset.saturating_sub(line_start),
+        }
+    }
+
     fn line_for_offset(&self, offset: usize) -> usize {
         match self.line_starts.binary_search(&offset) {
             Ok(line) => line,
@@ -123,3 +208,87 @@ impl LineIndex {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree_sitter::Parser;
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position {
+                    line: start.0,
+                    character: start.1,
+                },
+                end: Position {
+                    line: end.0,
+                    character: end.1,
+                },
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn ranged_changes_match_a_fresh_parse() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_amble::language())
+            .expect("load amble grammar");
+
+        let source = "room hall {\n    name \"Hall\"\n    desc \"A hall.\"\n}\n";
+        let mut document = Document::new(source.to_string());
+        document.set_tree(parser.parse(source, None).expect("parse"));
+
+        let edits = [
+            change((0, 5), (0, 9), "great-hall"),
+            change((2, 10), (2, 17), "A long,\nlong hall."),
+            change((5, 0), (5, 0), "\nroom cellar {\n    name \"Cellar\"\n}\n"),
+            change(
+                (1, 4),
+                (3, 10),
+                "name \"Great Hall\"\n    desc \"A long hall.",
+            ),
+            // Past the end of a line and of the document; both ends snap back.
+            change((6, 40), (9, 0), "\n}\n"),
+        ];
+        for edit in edits {
+            document.apply_change(edit);
+            let reparsed = parser
+                .parse(document.text(), document.tree())
+                .expect("incremental parse");
+            document.set_tree(reparsed);
+
+            let fresh = Document::new(document.text().to_string());
+            assert_eq!(
+                document.line_index.line_starts,
+                fresh.line_index.line_starts
+            );
+            let expected = parser.parse(document.text(), None).expect("fresh parse");
+            assert_eq!(
+                document.tree().expect("tree").root_node().to_sexp(),
+                expected.root_node().to_sexp()
+            );
+        }
+
+        assert_eq!(
+            document.text(),
+            "room great-hall {\n    name \"Great Hall\"\n    desc \"A long hall.\"\n}\n\nroom cellar {\n    name \"Cellar\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn full_change_replaces_text_and_drops_tree() {
+        let mut document = Document::new("room a {}".to_string());
+        document.apply_change(TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "room b {}\n".to_string(),
+        });
+        assert_eq!(document.text(), "room b {}\n");
+        assert!(document.tree().is_none());
+        assert_eq!(document.position_at(10).line, 1);
+    }
+}
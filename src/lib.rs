@@ -1,4 +1,4 @@
-use zed_extension_api::{self as zed, LanguageServerId, Result};
+use zed_extension_api::{self as zed, serde_json, settings::LspSettings, LanguageServerId, Result};
 
 struct AmbleExtension;
 
@@ -29,6 +29,28 @@ impl zed::Extension for AmbleExtension {
             env: Default::default(),
         })
     }
+
+    // Lets the inlay hint toggles apply from startup; falls back to `settings` so users only
+    // need to configure one of the two.
+    fn language_server_initialization_options(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        let settings = LspSettings::for_worktree(language_server_id.as_ref(), worktree)?;
+        Ok(settings.initialization_options.or(settings.settings))
+    }
+
+    // Sent to the server as `workspace/didChangeConfiguration` whenever `lsp.amble-lsp.settings`
+    // changes.
+    fn language_server_workspace_configuration(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        let settings = LspSettings::for_worktree(language_server_id.as_ref(), worktree)?;
+        Ok(settings.settings)
+    }
 }
 
 zed::register_extension!(AmbleExtension);